@@ -1,10 +1,14 @@
-use std::collections::HashMap;
+use std::f64::consts::PI;
 
 use rand::Rng;
 
 use crate::{
     c,
-    matrix::{complex::C, matrix::Matrix},
+    matrix::{
+        complex::C,
+        matrix::{hadamard, Matrix},
+    },
+    quantum_assembler::quantum_sim::{measure_partial_vec, measure_vec, measure_vec_seeded},
     util::{binary_string_to_int, index_to_binary_string, mod_power},
 };
 
@@ -110,21 +114,14 @@ fn get_m_probability_dist(m: Matrix, n_bits: usize) -> Vec<(usize, C)> {
         panic!("M should be a vector");
     }
 
-    let mut res: Vec<(usize, C)> = vec![];
-    for i in 1..m.size().0 {
-        let v = m.data[i][0];
-        if v == c!(0) {
-            continue;
-        }
-
-        let binary_string = index_to_binary_string(i, n_bits * 3);
-        let m_string = binary_string[0..(n_bits * 2)].to_string();
-        let m = binary_string_to_int(m_string);
-
-        res.push((m, v))
-    }
-
-    res
+    m.iter_nonzero(1e-9)
+        .filter(|&(i, _, _)| i > 0)
+        .map(|(i, _, v)| {
+            let binary_string = index_to_binary_string(i, n_bits * 3);
+            let m_string = binary_string[0..(n_bits * 2)].to_string();
+            (binary_string_to_int(m_string), v)
+        })
+        .collect()
 }
 
 fn get_n_probability_dist(m: Matrix, n_bits: usize) -> Vec<(usize, C)> {
@@ -132,21 +129,14 @@ fn get_n_probability_dist(m: Matrix, n_bits: usize) -> Vec<(usize, C)> {
         panic!("M should be a vector");
     }
 
-    let mut res: Vec<(usize, C)> = vec![];
-    for i in 1..m.size().0 {
-        let v = m.data[i][0];
-        if v == c!(0) {
-            continue;
-        }
-
-        let binary_string = index_to_binary_string(i, n_bits * 3);
-        let n_string = binary_string[(n_bits * 2)..(n_bits * 3)].to_string();
-        let n = binary_string_to_int(n_string);
-
-        res.push((n, v))
-    }
-
-    res
+    m.iter_nonzero(1e-9)
+        .filter(|&(i, _, _)| i > 0)
+        .map(|(i, _, v)| {
+            let binary_string = index_to_binary_string(i, n_bits * 3);
+            let n_string = binary_string[(n_bits * 2)..(n_bits * 3)].to_string();
+            (binary_string_to_int(n_string), v)
+        })
+        .collect()
 }
 
 fn get_m(binary_string: String, n_bits: usize) -> usize {
@@ -165,9 +155,7 @@ fn find_period(a: u32, n: u32) -> u32 {
 
     script.push_str("U TENSOR G_H G_H\n");
 
-    for _ in 0..(m_bits - 2) {
-        script.push_str("U TENSOR U G_H\n");
-    }
+    script.push_str(format!("TENSOR_N U G_H {}\n", m_bits - 2).as_str());
 
     let n_size = (2 as u32).clone().pow(n_bits.clone() as u32) as usize;
     script.push_str(format!("U TENSOR U G_I_{}\n", n_size).as_str());
@@ -228,37 +216,141 @@ fn find_factors(r: u32, a: u32, n: u32) -> Option<(u32, u32)> {
     return Some((g, n / g));
 }
 
-pub fn shors(n: u32) -> Option<(u32, u32)> {
+#[derive(Debug, Clone, PartialEq)]
+pub enum FactorizationResult {
+    Factors(u32, u32),
+    Prime,
+    // p^k = n
+    PrimePower(u32, u32),
+    Failed(String),
+}
+
+const MAX_ATTEMPTS: u32 = 10;
+
+fn is_prime(n: u32) -> bool {
+    n >= 2
+        && (2..)
+            .take_while(|d| d * d <= n)
+            .all(|d| !n.is_multiple_of(d))
+}
+
+fn prime_power(n: u32) -> Option<(u32, u32)> {
+    // Largest k first so 64 = 2^6 rather than 8^2
+    (2..=(32 - n.leading_zeros())).rev().find_map(|k| {
+        let p = (n as f64).powf(1.0 / k as f64).round() as u32;
+        (is_prime(p) && p.checked_pow(k) == Some(n)).then_some((p, k))
+    })
+}
+
+pub fn factorize(n: u32) -> FactorizationResult {
     // 0. Validate log2(n) < max_q_bits
+    if n < 4 {
+        return FactorizationResult::Failed(format!("{} has no non trivial factors", n));
+    }
 
-    // 1. Use polynomial to determine if n is power of a prime or a prime, if so return
-    // For now will skip and assume n is p * q with p and q both prime
+    // 1. Classically rule out primes, prime powers and even numbers
+    if is_prime(n) {
+        return FactorizationResult::Prime;
+    }
+    if let Some((p, k)) = prime_power(n) {
+        return FactorizationResult::PrimePower(p, k);
+    }
+    if n.is_multiple_of(2) {
+        return FactorizationResult::Factors(2, n / 2);
+    }
 
     // 2. Pick random number a < n
-    for i in 0..10 {
+    for _ in 0..MAX_ATTEMPTS {
         let a = pick_a(n);
 
         // 2.1 if gcd(a, n) != 1, a is a the factor of n we were looking for
         if gcd(a, n) != 1 {
-            return Some((gcd(a, n), n / gcd(a, n)));
+            return FactorizationResult::Factors(gcd(a, n), n / gcd(a, n));
         }
 
         // 3. Use quantum algorithm to find period r of a^x mod n
         let r = find_period(a, n);
         println!("a {}, for n {} => period {}", a, n, r);
 
-        let res = find_factors(r, a, n);
-        if res.is_none() {
-            return None; // TODO: SHOULD CONTINUE THE LOOP
+        // 4. An odd period or a^(r/2) = -1 mod n means this a is no good, try another
+        if let Some((p, q)) = find_factors(r, a, n) {
+            return FactorizationResult::Factors(p, q);
         }
+    }
 
-        return res;
+    FactorizationResult::Failed(format!(
+        "No valid period found for {} in {} attempts",
+        n, MAX_ATTEMPTS
+    ))
+}
+
+pub fn shors(n: u32) -> Option<(u32, u32)> {
+    match factorize(n) {
+        FactorizationResult::Factors(p, q) => Some((p, q)),
+        _ => None,
     }
-    panic!("COULD NOT FIND A VALID R")
+}
+
+fn grover_state(oracle: &Matrix, num_qubits: usize) -> Matrix {
+    let size = 2_usize.pow(num_qubits as u32);
+    assert_eq!(
+        oracle.size(),
+        (size, size),
+        "Invalid oracle, should act on {} qbits",
+        num_qubits
+    );
+
+    let start = hadamard()
+        .tensor_pow(num_qubits)
+        .multiply(&Matrix::zero(size, 1).set(0, 0, c!(1)));
+
+    // Inversion about the mean, 2|s><s| - I
+    let diffusion = start.outer_product(&start).scalar_mul(c!(2)) - Matrix::identity(size);
+
+    let iterations = (PI / 4.0 * (size as f64).sqrt()).floor() as usize;
+    (0..iterations).fold(start, |state, _| {
+        diffusion.multiply(&oracle.multiply(&state))
+    })
+}
+
+// Searches for the basis state marked by a phase oracle (-1 on the marked state)
+pub fn grover(oracle: &Matrix, num_qubits: usize) -> String {
+    measure_vec(&grover_state(oracle, num_qubits))
+}
+
+pub fn grover_seeded(oracle: &Matrix, num_qubits: usize, rng: &mut impl Rng) -> String {
+    measure_vec_seeded(&grover_state(oracle, num_qubits), rng)
+}
+
+// True if f is constant, given the oracle |x>|y> -> |x>|y xor f(x)> over n + 1 qbits
+pub fn deutsch_jozsa(oracle: &Matrix, n: usize) -> bool {
+    let size = 2_usize.pow(n as u32 + 1);
+    assert_eq!(
+        oracle.size(),
+        (size, size),
+        "Invalid oracle, should act on {} qbits",
+        n + 1
+    );
+
+    // |0...0>|1>
+    let start = Matrix::zero(size, 1).set(1, 0, c!(1));
+    let state = hadamard().tensor_pow(n + 1).multiply(&start);
+    let state = oracle.multiply(&state);
+    let state = hadamard()
+        .tensor_pow(n)
+        .tensor(&Matrix::identity(2))
+        .multiply(&state);
+
+    // The input register collapses to all zeros with certainty only for a constant f
+    measure_partial_vec(&state, 0, n as i32)
+        .iter_nonzero(1e-9)
+        .all(|(i, _, _)| i >> 1 == 0)
 }
 
 #[cfg(test)]
 mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
     use super::*;
 
     #[test]
@@ -276,6 +368,58 @@ mod tests {
         assert_eq!(p * q, n);
     }
 
+    #[test]
+    fn test_grover() {
+        let oracle = Matrix::identity(8).set(5, 5, c!(-1));
+
+        let hits = (0..20)
+            .filter(|&seed| grover_seeded(&oracle, 3, &mut StdRng::seed_from_u64(seed)) == "101")
+            .count();
+        assert!(hits >= 16, "only {} of 20 runs found 101", hits);
+
+        assert_eq!(grover(&oracle, 3).len(), 3);
+    }
+
+    // |x>|y> -> |x>|y xor f(x)>, y being the last qbit
+    fn function_oracle(n: usize, f: impl Fn(usize) -> usize) -> Matrix {
+        let size = 2_usize.pow(n as u32 + 1);
+        (0..size).fold(Matrix::zero(size, size), |oracle, i| {
+            let (x, y) = (i >> 1, i & 1);
+            oracle.set((x << 1) | (y ^ f(x)), i, c!(1))
+        })
+    }
+
+    #[test]
+    fn test_deutsch_jozsa() {
+        for n in 1..4 {
+            assert!(deutsch_jozsa(&function_oracle(n, |_| 0), n));
+            assert!(deutsch_jozsa(&function_oracle(n, |_| 1), n));
+
+            // Parity of the lowest input bit and of all input bits are both balanced
+            assert!(!deutsch_jozsa(&function_oracle(n, |x| x & 1), n));
+            assert!(!deutsch_jozsa(
+                &function_oracle(n, |x| x.count_ones() as usize % 2),
+                n
+            ));
+        }
+    }
+
+    #[test]
+    fn test_factorize() {
+        assert_eq!(factorize(6), FactorizationResult::Factors(2, 3));
+        assert_eq!(factorize(14), FactorizationResult::Factors(2, 7));
+
+        assert_eq!(factorize(13), FactorizationResult::Prime);
+        assert_eq!(factorize(97), FactorizationResult::Prime);
+
+        assert_eq!(factorize(27), FactorizationResult::PrimePower(3, 3));
+        assert_eq!(factorize(64), FactorizationResult::PrimePower(2, 6));
+        assert_eq!(factorize(49), FactorizationResult::PrimePower(7, 2));
+
+        assert!(matches!(factorize(1), FactorizationResult::Failed(_)));
+        assert_eq!(shors(13), None);
+    }
+
     #[test]
     fn test_gcd() {
         assert_eq!(gcd(10, 15), 5);
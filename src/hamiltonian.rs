@@ -0,0 +1,83 @@
+use crate::{
+    c,
+    matrix::{
+        complex::C,
+        matrix::{pauli_x, pauli_y, pauli_z, Matrix},
+    },
+};
+
+pub fn pauli_string(spec: &str, n_qubits: usize) -> Matrix {
+    // e.g. "XZ" -> X tensor Z, the first character acts on the most significant qbit
+    assert_eq!(
+        spec.len(),
+        n_qubits,
+        "Pauli string {} should have one operator per qbit",
+        spec
+    );
+
+    let mut operator = Matrix::identity(1);
+    for p in spec.chars() {
+        let pauli = match p {
+            'I' => Matrix::identity(2),
+            'X' => pauli_x(),
+            'Y' => pauli_y(),
+            'Z' => pauli_z(),
+            _ => panic!("Invalid Pauli operator {} in {}", p, spec),
+        };
+        operator = operator.tensor(&pauli);
+    }
+
+    operator
+}
+
+pub fn hamiltonian(terms: &[(f64, &str)]) -> Matrix {
+    // Weighted sum of Pauli strings, e.g. [(0.5, "XZ"), (1.0, "YI")] -> 0.5 XZ + YI
+    assert!(
+        !terms.is_empty(),
+        "Hamiltonian should have at least one term"
+    );
+
+    let n_qubits = terms[0].1.len();
+    let size = 2_usize.pow(n_qubits as u32);
+
+    terms
+        .iter()
+        .fold(Matrix::zero_sq(size), |acc, (weight, spec)| {
+            acc + pauli_string(spec, n_qubits).scalar_mul(c!(*weight))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pauli_string() {
+        assert_eq!(
+            pauli_string("IX", 2),
+            Matrix::identity(2).tensor(&pauli_x())
+        );
+        assert_eq!(
+            pauli_string("XZY", 3),
+            pauli_x().tensor(&pauli_z()).tensor(&pauli_y())
+        );
+        assert_eq!(pauli_string("", 0), Matrix::identity(1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_pauli_string_invalid() {
+        pauli_string("XA", 2);
+    }
+
+    #[test]
+    fn test_hamiltonian() {
+        let h = hamiltonian(&[(0.5, "XZ"), (1.0, "YI")]);
+
+        assert!(h.is_hermitian());
+        assert_eq!(
+            h,
+            pauli_string("XZ", 2).scalar_mul(c!(0.5)) + pauli_string("YI", 2)
+        );
+    }
+}
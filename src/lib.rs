@@ -1,5 +1,59 @@
-mod matrix;
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+pub mod matrix;
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
 mod util;
 
+#[cfg(test)]
+mod test_util;
+
+pub mod hamiltonian;
+
+#[cfg(feature = "std")]
+pub mod algorithms;
+#[cfg(feature = "std")]
 pub mod quantum_assembler;
-pub mod algorithms;
\ No newline at end of file
+
+// Exercises the core without the std feature: cargo test --no-default-features
+#[cfg(all(test, not(feature = "std")))]
+mod no_std_tests {
+    use alloc::vec;
+
+    use crate::{
+        c,
+        hamiltonian::pauli_string,
+        matrix::{
+            complex::C,
+            matrix::{cnot, hadamard, Matrix},
+        },
+    };
+
+    #[test]
+    fn test_complex_arithmetic() {
+        let x = c!(1, 2);
+        let y = c!(3, -1);
+
+        assert_eq!(x + y, c!(4, 1));
+        assert_eq!(x - y, c!(-2, 3));
+        assert_eq!(x * y, c!(5, 5));
+        assert_eq!((x * y) / y, x);
+        assert_eq!(c!(3, 4).modulus(), 5.0);
+        assert_eq!("1-2i".parse::<C>(), Ok(c!(1, -2)));
+    }
+
+    #[test]
+    fn test_matrix_ops() {
+        let h = hadamard();
+        assert!((h.clone() * h.clone()).is_close_to_identity(1e-9));
+        assert!(cnot().is_unitary());
+
+        let zero = Matrix::from_basis_string("00").unwrap();
+        let bell = cnot() * h.tensor(&Matrix::identity(2)) * zero;
+        assert!((bell.norm() - 1.0).abs() < 1e-9);
+        assert_eq!(bell.data[0], vec![bell.data[3][0]]);
+
+        assert_eq!(pauli_string("ZZ", 2) * bell.clone(), bell);
+    }
+}
@@ -1,7 +1,14 @@
-use std::ops::{Add, Div, Mul, Sub};
-use std::fmt;
+use alloc::{
+    format,
+    string::{String, ToString},
+};
+use core::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
+use core::str::FromStr;
+use core::{error, fmt};
 
 use crate::util::f64_equal;
+#[cfg(not(any(feature = "std", test)))]
+use crate::util::FloatExt;
 
 #[derive(Copy, Clone)]
 pub struct C {
@@ -11,8 +18,17 @@ pub struct C {
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct CPolar {
-    r: f64,
-    t: f64,
+    pub r: f64,
+    pub t: f64,
+}
+
+impl CPolar {
+    pub fn new<T: Into<f64>>(r: T, t: T) -> CPolar {
+        CPolar {
+            r: r.into(),
+            t: t.into(),
+        }
+    }
 }
 
 impl PartialEq for C {
@@ -43,6 +59,26 @@ impl Sub for C {
     }
 }
 
+impl AddAssign for C {
+    fn add_assign(&mut self, other: C) {
+        self.a += other.a;
+        self.b += other.b;
+    }
+}
+
+impl SubAssign for C {
+    fn sub_assign(&mut self, other: C) {
+        self.a -= other.a;
+        self.b -= other.b;
+    }
+}
+
+impl MulAssign for C {
+    fn mul_assign(&mut self, other: C) {
+        *self = *self * other;
+    }
+}
+
 impl Div for C {
     type Output = C;
 
@@ -65,6 +101,17 @@ impl Mul for C {
     }
 }
 
+impl Neg for C {
+    type Output = C;
+
+    fn neg(self) -> C {
+        C {
+            a: -self.a,
+            b: -self.b,
+        }
+    }
+}
+
 impl fmt::Debug for C {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if f64_equal(self.b, 0.0) {
@@ -74,6 +121,79 @@ impl fmt::Debug for C {
     }
 }
 
+// a+bi form, e.g. 0.5-0.5i, 2, -i, honouring the precision flag: {:.3}
+impl fmt::Display for C {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let c = self.normalize_zeros();
+        let part = |v: f64| match f.precision() {
+            Some(p) => format!("{:.*}", p, v),
+            None => format!("{}", v),
+        };
+
+        let imag = if c.b == 1.0 {
+            "i".to_string()
+        } else if c.b == -1.0 {
+            "-i".to_string()
+        } else {
+            format!("{}i", part(c.b))
+        };
+
+        match (c.a == 0.0, c.b == 0.0) {
+            (_, true) => write!(f, "{}", part(c.a)),
+            (true, false) => write!(f, "{}", imag),
+            (false, false) if imag.starts_with('-') => write!(f, "{}{}", part(c.a), imag),
+            (false, false) => write!(f, "{}+{}", part(c.a), imag),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseComplexError(String);
+
+impl fmt::Display for ParseComplexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid complex number: {}", self.0)
+    }
+}
+
+impl error::Error for ParseComplexError {}
+
+impl FromStr for C {
+    type Err = ParseComplexError;
+
+    // Accepts a, bi, a+bi and a-bi with optional signs, e.g. -1-2i, and a unit i as in 3-i
+    fn from_str(s: &str) -> Result<C, ParseComplexError> {
+        let err = || ParseComplexError(s.to_string());
+        let inp = s.trim();
+
+        let parse_part = |part: &str| part.parse::<f64>().map_err(|_| err());
+        let parse_imag = |part: &str| match part {
+            "" | "+" => Ok(1.0),
+            "-" => Ok(-1.0),
+            _ => parse_part(part),
+        };
+
+        match inp.strip_suffix('i') {
+            Some(rest) => {
+                // Split on the last sign that is not leading and not part of an exponent
+                let split = rest
+                    .char_indices()
+                    .filter(|(i, c)| {
+                        (*c == '+' || *c == '-') && *i > 0 && !rest[..*i].ends_with(['e', 'E'])
+                    })
+                    .map(|(i, _)| i)
+                    .next_back();
+
+                match split {
+                    Some(i) => Ok(C::new(parse_part(&rest[..i])?, parse_imag(&rest[i..])?)),
+                    None => Ok(C::new(0.0, parse_imag(rest)?)),
+                }
+            }
+            None => Ok(C::new(parse_part(inp)?, 0.0)),
+        }
+    }
+}
+
 impl C {
     pub fn new<T: Into<f64> + Copy>(a: T, b: T) -> C {
         C {
@@ -89,6 +209,19 @@ impl C {
         }
     }
 
+    // Bitwise comparison, `==` tolerates floating point error through f64_equal
+    pub fn exact_eq(&self, other: &C) -> bool {
+        self.a.to_bits() == other.a.to_bits() && self.b.to_bits() == other.b.to_bits()
+    }
+
+    // Replaces -0.0 parts by 0.0, NaN parts are left untouched
+    pub fn normalize_zeros(self) -> C {
+        C {
+            a: self.a + 0.0,
+            b: self.b + 0.0,
+        }
+    }
+
     pub fn modulus(self) -> f64 {
         (self.a * self.a + self.b * self.b).sqrt()
     }
@@ -105,21 +238,38 @@ impl C {
          C::new(r.sqrt(), 0.0) * (self + C::new(r, 0.0)) / C::new((self + C::new(r, 0.0)).modulus(), 0.0)
     }
 
+    // e^(a + bi) = e^a (cos b + i sin b)
+    pub fn exp(self) -> C {
+        C::from_polar(CPolar {
+            r: self.a.exp(),
+            t: self.b,
+        })
+    }
+
+    // Principal branch ln(r) + i theta, theta in (-pi, pi]
+    pub fn ln(self) -> C {
+        let p = self.to_polar();
+        C::new(p.r.ln(), p.t)
+    }
+
     pub fn to_polar(self) -> CPolar {
         CPolar {
             r: self.modulus(),
-            t: (self.b / self.a).atan(),
+            t: self.b.atan2(self.a),
         }
     }
     
-    pub fn pow(&self, pow: usize) -> C {
-        if pow == 0 {
-            return C::new(1, 0);
-        }
-
-        let mut res = self.clone();
-        for _ in 1..pow {
-            res = res * self.clone();
+    // Integer power by repeated squaring, self^0 = 1 (also for 0^0)
+    pub fn pow(self, exp: usize) -> C {
+        let mut res = C::new(1, 0);
+        let mut base = self;
+        let mut exp = exp;
+        while exp > 0 {
+            if exp & 1 == 1 {
+                res *= base;
+            }
+            base *= base;
+            exp >>= 1;
         }
         res
     }
@@ -135,6 +285,13 @@ macro_rules! c {
     };
 }
 
+#[macro_export]
+macro_rules! c_polar {
+    ($r: expr, $t: expr) => {
+        CPolar::new($r as f64, $t as f64)
+    };
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -151,6 +308,24 @@ mod tests {
         assert_ne!(c!(2, 1), c!(1, 2));
     }
 
+    #[test]
+    fn tolerant_equality() {
+        let third = c!(1.0 / 3.0) * c!(3);
+        assert_eq!(third, c!(1));
+        assert_eq!(c!(2.0_f64.sqrt()) * c!(2.0_f64.sqrt()), c!(2));
+        assert_ne!(c!(1), c!(1.0 + 1e-6));
+
+        assert!(c!(1, -2).exact_eq(&c!(1, -2)));
+        assert!(!c!(0.1 + 0.2).exact_eq(&c!(0.3)));
+        assert_eq!(c!(0.1 + 0.2), c!(0.3));
+
+        assert_eq!(c!(-0.0), c!(0.0));
+        assert_eq!(c!(-0.0, -0.0), c!(0));
+        assert!(c!(-0.0, -0.0).normalize_zeros().exact_eq(&c!(0.0, 0.0)));
+        assert!(c!(f64::NAN).normalize_zeros().a.is_nan());
+        assert_ne!(c!(f64::NAN), c!(f64::NAN));
+    }
+
     #[test]
     fn add() {
         assert_eq!(c!(1, 1) + c!(1, 1), c!(2, 2));
@@ -170,6 +345,27 @@ mod tests {
         assert_eq!(c!(2, 1) - c!(1, 2), c!(1, -1));
     }
 
+    #[test]
+    fn neg() {
+        assert_eq!(-c!(1, -2), c!(-1, 2));
+        assert_eq!(-(-c!(3, 4)), c!(3, 4));
+        assert_eq!(-c!(0), c!(0));
+    }
+
+    #[test]
+    fn assign_ops() {
+        let mut z = c!(1, 1);
+        z += c!(2, 3);
+        assert_eq!(z, c!(3, 4));
+
+        z -= c!(1, 5);
+        assert_eq!(z, c!(2, -1));
+
+        z *= c!(0, 1);
+        assert_eq!(z, c!(1, 2));
+        assert_eq!(z, (c!(1, 1) + c!(2, 3) - c!(1, 5)) * c!(0, 1));
+    }
+
     #[test]
     fn div() {
         assert_eq!(c!(-2, 1) / c!(1, 2), c!(0, 1));
@@ -197,6 +393,49 @@ mod tests {
         );
     }
 
+    #[test]
+    fn to_polar_quadrants() {
+        use core::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+        let angle = |x: C| x.to_polar().t;
+        assert!(f64_equal(angle(c!(1, 1)), FRAC_PI_4));
+        assert!(f64_equal(angle(c!(-1, 1)), 3.0 * FRAC_PI_4));
+        assert!(f64_equal(angle(c!(-1, -1)), -3.0 * FRAC_PI_4));
+        assert!(f64_equal(angle(c!(1, -1)), -FRAC_PI_4));
+
+        assert!(f64_equal(angle(c!(1, 0)), 0.0));
+        assert!(f64_equal(angle(c!(-1, 0)), PI));
+        assert!(f64_equal(angle(c!(0, 1)), FRAC_PI_2));
+        assert!(f64_equal(angle(c!(0, -1)), -FRAC_PI_2));
+
+        for x in [c!(-2, 1), c!(-3, -4), c!(0, -2), c!(-1, 0)] {
+            assert_eq!(C::from_polar(x.to_polar()), x);
+        }
+    }
+
+    #[test]
+    fn exp_ln() {
+        use core::f64::consts::{FRAC_PI_2, PI};
+
+        assert_eq!(c!(0.0, PI).exp(), c!(-1, 0));
+        assert_eq!(c!(0.0, FRAC_PI_2).exp(), c!(0, 1));
+        assert_eq!(c!(1).exp(), c!(core::f64::consts::E));
+        assert_eq!(c!(-1).ln(), c!(0.0, PI));
+
+        for z in [c!(2, 1), c!(-3, -4), c!(0.5, -2.0), c!(-1, 0)] {
+            assert_eq!(z.ln().exp(), z);
+        }
+    }
+
+    #[test]
+    fn c_polar_macro() {
+        use core::f64::consts::PI;
+
+        assert_eq!(C::from_polar(c_polar!(2.0, PI / 2.0)), c!(0, 2));
+        assert_eq!(C::from_polar(c_polar!(1, 0)), c!(1));
+        assert_eq!(c_polar!(3, 1), CPolar { r: 3.0, t: 1.0 });
+    }
+
     #[test]
     fn from_polar() {
         assert_eq!(C::from_polar(c!(2, 1).to_polar()), c!(2, 1));
@@ -209,10 +448,83 @@ mod tests {
         assert!(root.b - 2.12 < 0.01);
     }
 
+    #[test]
+    fn display() {
+        assert_eq!(c!(2).to_string(), "2");
+        assert_eq!(c!(-0.5).to_string(), "-0.5");
+        assert_eq!(c!(0).to_string(), "0");
+        assert_eq!(c!(-0.0, -0.0).to_string(), "0");
+        assert_eq!(c!(-2.0, -0.0).to_string(), "-2");
+
+        assert_eq!(c!(0, 1).to_string(), "i");
+        assert_eq!(c!(0, -1).to_string(), "-i");
+        assert_eq!(c!(0.0, 2.5).to_string(), "2.5i");
+
+        assert_eq!(c!(0.5, -0.5).to_string(), "0.5-0.5i");
+        assert_eq!(c!(1, 1).to_string(), "1+i");
+        assert_eq!(c!(-1, -3).to_string(), "-1-3i");
+
+        assert_eq!(format!("{:.3}", c!(1.0 / 3.0, 0.0)), "0.333");
+        assert_eq!(format!("{:.2}", c!(1.0, -2.0 / 3.0)), "1.00-0.67i");
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("1".parse::<C>().unwrap(), c!(1));
+        assert_eq!("-0.5".parse::<C>().unwrap(), c!(-0.5));
+        assert_eq!("2i".parse::<C>().unwrap(), c!(0, 2));
+        assert_eq!("1+2i".parse::<C>().unwrap(), c!(1, 2));
+        assert_eq!("1-2i".parse::<C>().unwrap(), c!(1, -2));
+        assert_eq!("-1-2i".parse::<C>().unwrap(), c!(-1, -2));
+        assert_eq!("1e-3+2.5i".parse::<C>().unwrap(), c!(0.001, 2.5));
+        assert_eq!(" 0.5-0.5i ".parse::<C>().unwrap(), c!(0.5, -0.5));
+        assert_eq!("-2i".parse::<C>().unwrap(), c!(0, -2));
+        assert_eq!("3+4i".parse::<C>().unwrap(), c!(3, 4));
+
+        assert_eq!("i".parse::<C>().unwrap(), c!(0, 1));
+        assert_eq!("-i".parse::<C>().unwrap(), c!(0, -1));
+        assert_eq!("+i".parse::<C>().unwrap(), c!(0, 1));
+        assert_eq!("3-i".parse::<C>().unwrap(), c!(3, -1));
+
+        // Round trip through Display
+        for x in [
+            c!(0.5, -0.5),
+            c!(0, 1),
+            c!(0, -1),
+            c!(2),
+            c!(-1, 1),
+            c!(0.0, 2.5),
+        ] {
+            assert_eq!(x.to_string().parse::<C>().unwrap(), x);
+        }
+    }
+
+    #[test]
+    fn test_from_str_malformed() {
+        assert_eq!(
+            "1+2j".parse::<C>(),
+            Err(ParseComplexError("1+2j".to_string()))
+        );
+        assert!("".parse::<C>().is_err());
+        assert!("1+2+3i".parse::<C>().is_err());
+        assert!("abc".parse::<C>().is_err());
+        assert!("ii".parse::<C>().is_err());
+        assert!("1+-i".parse::<C>().is_err());
+    }
+
     #[test]
     fn test_pow() {
         let c = c!(2);
         let res = c.pow(2);
         assert_eq!(res, c!(4));
+        assert_eq!(c!(1, 1).pow(3), c!(-2, 2));
+        assert_eq!(c!(0).pow(0), c!(1));
+
+        // The powers of i used by the QFT
+        let i = c!(0, 1);
+        let expected = [c!(1), c!(0, 1), c!(-1, 0), c!(0, -1)];
+        for exp in 0..=9 {
+            assert_eq!(i.pow(exp), expected[exp % 4]);
+        }
     }
 }
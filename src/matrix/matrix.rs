@@ -1,17 +1,77 @@
-use std::ops::{Add, Mul};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{
+    error, fmt,
+    ops::{Add, Mul, Neg, Range, Sub},
+};
 
+#[cfg(not(any(feature = "std", test)))]
+use crate::util::FloatExt;
 use crate::{
     c,
-    util::{min_bit_size, mod_power},
+    util::{binary_string_to_int, f64_equal, min_bit_size, mod_power},
 };
 
 use super::complex::C;
 
+// Smallest number of rows for which multiply splits the rows over threads
+#[cfg(feature = "parallel")]
+const PARALLEL_MIN_ROWS: usize = 64;
+
+// Default tolerance of is_unitary and is_hermitian
+pub const UNITARY_EPS: f64 = 1e-9;
+
+// Both parts +-0, unlike C's PartialEq this has no tolerance
+fn is_exact_zero(v: C) -> bool {
+    v.a == 0.0 && v.b == 0.0
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Matrix {
     pub data: Vec<Vec<C>>,
 }
 
+#[derive(Debug)]
+pub enum MatrixError {
+    ZeroNorm(f64),
+    InvalidBasisString(String),
+    DimensionMismatch((usize, usize), (usize, usize)),
+    Empty,
+    InvalidPermutation(Vec<usize>),
+}
+
+impl fmt::Display for MatrixError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MatrixError::ZeroNorm(norm) => {
+                write!(
+                    f,
+                    "Cannot normalize a matrix with (near) zero norm: {:e}",
+                    norm
+                )
+            }
+            MatrixError::InvalidBasisString(bits) => {
+                write!(f, "Invalid basis string {}, expected only 0 and 1", bits)
+            }
+            MatrixError::DimensionMismatch((rows, cols), (other_rows, other_cols)) => write!(
+                f,
+                "Dimension mismatch between a {}x{} and a {}x{} matrix",
+                rows, cols, other_rows, other_cols
+            ),
+            MatrixError::Empty => write!(f, "Matrix has no entries"),
+            MatrixError::InvalidPermutation(mapping) => {
+                write!(f, "Mapping {:?} is not a permutation", mapping)
+            }
+        }
+    }
+}
+
+impl error::Error for MatrixError {}
+
 impl Add for Matrix {
     type Output = Matrix;
 
@@ -19,7 +79,7 @@ impl Add for Matrix {
         assert_eq!(self.data.len(), other.data.len());
         assert_eq!(self.data[0].len(), other.data[0].len());
 
-        let mut data = vec![vec![c!(0); self.data.len()]; self.data[0].len()];
+        let mut data = vec![vec![c!(0); self.data[0].len()]; self.data.len()];
         for i in 0..self.data.len() {
             for j in 0..self.data[0].len() {
                 data[i][j] = self.data[i][j] + other.data[i][j];
@@ -29,6 +89,35 @@ impl Add for Matrix {
     }
 }
 
+impl Sub for Matrix {
+    type Output = Matrix;
+
+    fn sub(self, other: Matrix) -> Matrix {
+        self + (-other)
+    }
+}
+
+impl Neg for &Matrix {
+    type Output = Matrix;
+
+    fn neg(self) -> Matrix {
+        let data = self
+            .data
+            .iter()
+            .map(|row| row.iter().map(|v| -*v).collect())
+            .collect();
+        Matrix { data }
+    }
+}
+
+impl Neg for Matrix {
+    type Output = Matrix;
+
+    fn neg(self) -> Matrix {
+        -&self
+    }
+}
+
 impl Mul for &Matrix {
     type Output = Matrix;
 
@@ -67,12 +156,104 @@ impl Matrix {
         Matrix { data }
     }
 
-    pub fn set(&self, row: usize, col: usize, value: C) -> Matrix {
-        let mut data = self.data.clone();
-        data[row][col] = value;
+    pub fn from_basis_string(bits: &str) -> Result<Matrix, MatrixError> {
+        // e.g. "0101" -> |0101>, the first character is the most significant qbit
+        if !bits.chars().all(|b| b == '0' || b == '1') {
+            return Err(MatrixError::InvalidBasisString(bits.to_string()));
+        }
+
+        let size = 2_usize.pow(bits.len() as u32);
+        let index = binary_string_to_int(bits.to_string());
+        Ok(Matrix::zero(size, 1).set(index, 0, c!(1)))
+    }
+
+    // Permutation matrix sending basis state i to basis state mapping[i]
+    pub fn permutation(mapping: &[usize]) -> Result<Matrix, MatrixError> {
+        let mut seen = vec![false; mapping.len()];
+        for target in mapping {
+            if *target >= mapping.len() || seen[*target] {
+                return Err(MatrixError::InvalidPermutation(mapping.to_vec()));
+            }
+            seen[*target] = true;
+        }
+        if mapping.is_empty() {
+            return Err(MatrixError::Empty);
+        }
+
+        let mut matrix = Matrix::zero_sq(mapping.len());
+        for (i, target) in mapping.iter().enumerate() {
+            matrix.data[*target][i] = c!(1);
+        }
+        Ok(matrix)
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> C {
+        self.data[row][col]
+    }
+
+    pub fn row(&self, i: usize) -> Vec<C> {
+        self.data[i].clone()
+    }
+
+    pub fn col(&self, j: usize) -> Vec<C> {
+        self.data.iter().map(|row| row[j]).collect()
+    }
+
+    pub fn submatrix(&self, rows: Range<usize>, cols: Range<usize>) -> Matrix {
+        assert!(
+            rows.start < rows.end && cols.start < cols.end,
+            "Submatrix requires non-empty ranges"
+        );
+        let data = self.data[rows]
+            .iter()
+            .map(|row| row[cols.clone()].to_vec())
+            .collect::<Vec<Vec<C>>>();
         Matrix { data }
     }
 
+    // Places other to the right of self, NB unlike the assembler's CONCAT this does not multiply
+    pub fn hstack(&self, other: &Matrix) -> Matrix {
+        assert_eq!(
+            self.data.len(),
+            other.data.len(),
+            "hstack requires equal row counts, got {:?} and {:?}",
+            self.size(),
+            other.size()
+        );
+        let data = self
+            .data
+            .iter()
+            .zip(&other.data)
+            .map(|(row, other_row)| [row.as_slice(), other_row.as_slice()].concat())
+            .collect::<Vec<Vec<C>>>();
+        Matrix { data }
+    }
+
+    // Places other below self
+    pub fn vstack(&self, other: &Matrix) -> Matrix {
+        assert_eq!(
+            self.data[0].len(),
+            other.data[0].len(),
+            "vstack requires equal column counts, got {:?} and {:?}",
+            self.size(),
+            other.size()
+        );
+        Matrix {
+            data: [self.data.as_slice(), other.data.as_slice()].concat(),
+        }
+    }
+
+    // Copying variant of set_mut, prefer set_mut when updating many entries in a loop
+    pub fn set(&self, row: usize, col: usize, value: C) -> Matrix {
+        let mut res = self.clone();
+        res.set_mut(row, col, value);
+        res
+    }
+
+    pub fn set_mut(&mut self, row: usize, col: usize, value: C) {
+        self.data[row][col] = value;
+    }
+
     pub fn identity(size: usize) -> Matrix {
         let mut data = vec![vec![c!(0); size]; size];
         for i in 0..size {
@@ -105,19 +286,28 @@ impl Matrix {
         self.conjugate().transpose()
     }
 
-    pub fn normalized(&self) -> Matrix {
+    pub fn normalized(&self) -> Result<Matrix, MatrixError> {
         let norm = self.norm();
-        self.scalar_mul(c!(1.0 / norm))
+        if f64_equal(norm, 0.0) {
+            // 1 / norm would blow up to inf / NaN amplitudes
+            return Err(MatrixError::ZeroNorm(norm));
+        }
+        Ok(self.scalar_mul(c!(1.0 / norm)).checked())
+    }
+
+    // Panicking variant of normalized, for states known to have a non-zero amplitude
+    pub fn renormalized(&self) -> Matrix {
+        self.normalized()
+            .unwrap_or_else(|e| panic!("Cannot renormalize: {}", e))
     }
 
+    pub fn is_normalized(&self, eps: f64) -> bool {
+        (self.norm() - 1.0).abs() <= eps
+    }
+
+    #[deprecated(note = "negates every entry, use `-matrix` instead")]
     pub fn negative_inverse(&self) -> Matrix {
-        let mut data = self.data.clone();
-        for i in 0..self.data.len() {
-            for j in 0..self.data[0].len() {
-                data[i][j] = c!(-1) * self.data[i][j];
-            }
-        }
-        Matrix { data }
+        -self
     }
 
     pub fn scalar_mul(&self, scalar: C) -> Matrix {
@@ -131,71 +321,485 @@ impl Matrix {
     }
 
     pub fn multiply(&self, other: &Matrix) -> Matrix {
-        assert_eq!(self.data[0].len(), other.data.len());
+        match self.try_multiply(other) {
+            Ok(res) => res,
+            Err(e) => panic!("{}", e),
+        }
+    }
 
-        let mut data = vec![vec![c!(0); other.data[0].len()]; self.data.len()];
-        for i in 0..self.data.len() {
-            for j in 0..other.data[0].len() {
-                for k in 0..self.data[0].len() {
-                    data[i][j] = data[i][j] + self.data[i][k] * other.data[k][j];
-                }
+    pub fn try_multiply(&self, other: &Matrix) -> Result<Matrix, MatrixError> {
+        if self.is_empty() || other.is_empty() {
+            return Err(MatrixError::Empty);
+        }
+        if self.data[0].len() != other.data.len() {
+            return Err(MatrixError::DimensionMismatch(self.size(), other.size()));
+        }
+
+        // Gates are mostly zero so skipping the zero terms makes most products far
+        // cheaper. A zero times a non-finite entry is NaN though, so with non-finite
+        // inputs every term is kept and checked() still catches the result
+        let skip_zeros = self.is_finite() && other.is_finite();
+        let self_nonzero = self.nonzero_columns(skip_zeros);
+        let other_nonzero = other.nonzero_columns(skip_zeros);
+
+        #[cfg(feature = "parallel")]
+        if self.data.len() >= PARALLEL_MIN_ROWS {
+            use rayon::prelude::*;
+
+            let data = (0..self.data.len())
+                .into_par_iter()
+                .map(|i| self.product_row(other, i, &self_nonzero[i], &other_nonzero))
+                .collect();
+            return Ok(Matrix { data }.checked());
+        }
+
+        let data = (0..self.data.len())
+            .map(|i| self.product_row(other, i, &self_nonzero[i], &other_nonzero))
+            .collect();
+        Ok(Matrix { data }.checked())
+    }
+
+    // Row i of self * other, shared by the serial and parallel paths so both sum in
+    // the same order and give bit-identical results. Every entry still sums its terms
+    // in increasing k, and the skipped terms are +-0 which cannot change a sum that
+    // starts at +0
+    fn product_row(
+        &self,
+        other: &Matrix,
+        i: usize,
+        self_nonzero: &[usize],
+        other_nonzero: &[Vec<usize>],
+    ) -> Vec<C> {
+        let mut row = vec![c!(0); other.data[0].len()];
+        for &k in self_nonzero {
+            for &j in &other_nonzero[k] {
+                row[j] += self.data[i][k] * other.data[k][j];
             }
         }
-        Matrix { data }
+        row
+    }
+
+    // Per row the column indices kept by product_row, every column if not skip_zeros
+    fn nonzero_columns(&self, skip_zeros: bool) -> Vec<Vec<usize>> {
+        self.data
+            .iter()
+            .map(|row| {
+                (0..row.len())
+                    .filter(|&j| !skip_zeros || !is_exact_zero(row[j]))
+                    .collect()
+            })
+            .collect()
     }
 
+    fn is_finite(&self) -> bool {
+        self.data
+            .iter()
+            .flatten()
+            .all(|v| v.a.is_finite() && v.b.is_finite())
+    }
+
+    // Bilinear sum of entrywise products, NB no conjugation so this is not the inner
+    // product of quantum states
+    #[deprecated(note = "bilinear product without conjugation, use `inner_product` for states")]
     pub fn dot(&self, other: Matrix) -> C {
         let mut sum = c!(0);
         for i in 0..self.data.len() {
             for j in 0..self.data[0].len() {
-                sum = sum + self.data[i][j] * other.data[i][j];
+                sum += self.data[i][j] * other.data[i][j];
+            }
+        }
+        sum
+    }
+
+    pub fn inner_product(&self, other: &Matrix) -> C {
+        // <self|other>, conjugating self so <v|v> is real and non-negative
+        assert_eq!(
+            self.size(),
+            other.size(),
+            "Inner product requires matrices of equal size"
+        );
+
+        let mut sum = c!(0);
+        for i in 0..self.data.len() {
+            for j in 0..self.data[0].len() {
+                sum += self.data[i][j].conjugate() * other.data[i][j];
             }
         }
         sum
     }
 
+    pub fn outer_product(&self, other: &Matrix) -> Matrix {
+        // |self><other|
+        assert!(
+            self.is_vector() && other.is_vector(),
+            "Outer product requires two column vectors"
+        );
+        self.multiply(&other.adjoint())
+    }
+
+    pub fn trace(&self) -> C {
+        assert_eq!(
+            self.size().0,
+            self.size().1,
+            "Trace requires a square matrix"
+        );
+        (0..self.data.len()).fold(c!(0), |sum, i| sum + self.data[i][i])
+    }
+
+    // Entries with a modulus above eps, as (row, column, value)
+    pub fn iter_nonzero(&self, eps: f64) -> impl Iterator<Item = (usize, usize, C)> + '_ {
+        self.data.iter().enumerate().flat_map(move |(i, row)| {
+            row.iter()
+                .enumerate()
+                .filter(move |(_, v)| v.modulus() > eps)
+                .map(move |(j, v)| (i, j, *v))
+        })
+    }
+
+    pub fn inverse(&self) -> Option<Matrix> {
+        // Gauss-Jordan elimination on [A | I] with partial pivoting
+        let size = self.data.len();
+        if size != self.data[0].len() {
+            return None;
+        }
+
+        let mut data = self.data.clone();
+        let mut inv = Matrix::identity(size).data;
+        for k in 0..size {
+            let pivot_row = (k..size)
+                .max_by(|&x, &y| data[x][k].modulus().total_cmp(&data[y][k].modulus()))
+                .unwrap();
+            if f64_equal(data[pivot_row][k].modulus(), 0.0) {
+                return None;
+            }
+            data.swap(k, pivot_row);
+            inv.swap(k, pivot_row);
+
+            let pivot = data[k][k];
+            for j in 0..size {
+                data[k][j] = data[k][j] / pivot;
+                inv[k][j] = inv[k][j] / pivot;
+            }
+
+            for i in (0..size).filter(|&i| i != k) {
+                let factor = data[i][k];
+                for j in 0..size {
+                    let (d, v) = (data[k][j], inv[k][j]);
+                    data[i][j] -= factor * d;
+                    inv[i][j] -= factor * v;
+                }
+            }
+        }
+
+        Some(Matrix { data: inv })
+    }
+
+    pub fn determinant(&self) -> C {
+        assert_eq!(
+            self.size().0,
+            self.size().1,
+            "Determinant requires a square matrix"
+        );
+
+        // LU decomposition with partial pivoting, det = (-1)^swaps * prod(U_kk)
+        let mut data = self.data.clone();
+        let size = data.len();
+        let mut det = c!(1);
+        for k in 0..size {
+            let pivot_row = (k..size)
+                .max_by(|&x, &y| data[x][k].modulus().total_cmp(&data[y][k].modulus()))
+                .unwrap();
+            if f64_equal(data[pivot_row][k].modulus(), 0.0) {
+                return c!(0);
+            }
+            if pivot_row != k {
+                data.swap(k, pivot_row);
+                det = -det;
+            }
+
+            let pivot = data[k][k];
+            det *= pivot;
+
+            let (upper, lower) = data.split_at_mut(k + 1);
+            let pivot_row = &upper[k];
+            for row in lower {
+                let factor = row[k] / pivot;
+                for (v, p) in row[k + 1..].iter_mut().zip(&pivot_row[k + 1..]) {
+                    *v -= factor * *p;
+                }
+            }
+        }
+        det
+    }
+
+    pub fn hilbert_schmidt(&self, other: &Matrix) -> C {
+        // Tr(A^dagger B)
+        assert_eq!(
+            self.size(),
+            other.size(),
+            "Hilbert-Schmidt product requires operators of equal size"
+        );
+        assert_eq!(
+            self.size().0,
+            self.size().1,
+            "Hilbert-Schmidt product requires square operators"
+        );
+
+        (self.adjoint() * other.clone()).trace()
+    }
+
+    pub fn hilbert_schmidt_normalized(&self, other: &Matrix) -> C {
+        self.hilbert_schmidt(other) / c!(self.data.len())
+    }
+
     pub fn tensor(&self, other: &Matrix) -> Matrix {
+        // Identity operands are common when embedding gates, skip the general product for them.
+        // Entries are copied rather than multiplied by 1 and 0, so only the sign of zero
+        // entries can differ from the general product
+        if self.is_exact_identity() {
+            return if self.data.len() == 1 {
+                other.clone()
+            } else {
+                other.kron_identity_left(self.data.len())
+            };
+        }
+        if other.is_exact_identity() {
+            return if other.data.len() == 1 {
+                self.clone()
+            } else {
+                self.kron_identity_right(other.data.len())
+            };
+        }
+
         let rows = self.data.len() * other.data.len();
         let cols = self.data[0].len() * other.data[0].len();
 
-        let mut data = vec![vec![c!(0); cols]; rows];
+        // Row i * rows_other + k is row k of other scaled by each entry of row i of self in turn
+        let mut data = Vec::with_capacity(rows);
+        for self_row in &self.data {
+            for other_row in &other.data {
+                let mut row = Vec::with_capacity(cols);
+                for a in self_row {
+                    row.extend(other_row.iter().map(|b| *a * *b));
+                }
+                data.push(row);
+            }
+        }
+        Matrix { data }.checked()
+    }
+
+    pub fn try_tensor(&self, other: &Matrix) -> Result<Matrix, MatrixError> {
+        if self.is_empty() || other.is_empty() {
+            return Err(MatrixError::Empty);
+        }
+        Ok(self.tensor(other))
+    }
 
-        let nr_rows_other = other.data.len();
-        let nr_cols_other = other.data[0].len();
+    fn is_empty(&self) -> bool {
+        self.data.is_empty() || self.data[0].is_empty()
+    }
 
-        for i in 0..rows {
-            for j in 0..cols {
-                let row = i / nr_rows_other;
-                let col = j / nr_cols_other;
+    fn is_exact_identity(&self) -> bool {
+        self.data.iter().enumerate().all(|(i, row)| {
+            row.len() == self.data.len()
+                && row
+                    .iter()
+                    .enumerate()
+                    .all(|(j, v)| v.a == if i == j { 1.0 } else { 0.0 } && v.b == 0.0)
+        })
+    }
 
-                let row2 = i % nr_rows_other;
-                let col2 = j % nr_cols_other;
+    // I_n tensor self: n copies of self along the diagonal
+    fn kron_identity_left(&self, n: usize) -> Matrix {
+        let (rows, cols) = self.size();
+        let mut data = vec![vec![c!(0); cols * n]; rows * n];
+        for block in 0..n {
+            for (i, row) in self.data.iter().enumerate() {
+                data[block * rows + i][block * cols..(block + 1) * cols].copy_from_slice(row);
+            }
+        }
+        Matrix { data }.checked()
+    }
 
-                data[i][j] = self.data[row][col] * other.data[row2][col2];
+    // self tensor I_n: every entry of self spread over the diagonal of an n x n block
+    fn kron_identity_right(&self, n: usize) -> Matrix {
+        let (rows, cols) = self.size();
+        let mut data = vec![vec![c!(0); cols * n]; rows * n];
+        for (i, row) in self.data.iter().enumerate() {
+            for (j, v) in row.iter().enumerate() {
+                for k in 0..n {
+                    data[i * n + k][j * n + k] = *v;
+                }
             }
         }
-        Matrix { data }
+        Matrix { data }.checked()
     }
 
-    pub fn norm(&self) -> f64 {
-        let mut norm = 0.0;
-        for i in 0..self.data.len() {
-            for j in 0..self.data[0].len() {
-                norm = norm + self.data[i][j].modulus().powf(2.0);
+    // Number of qbits a gate acts on, panics unless it is square with a power of two size
+    fn gate_qubits(&self) -> usize {
+        let rows = self.data.len();
+        let cols = self.data.first().map_or(0, |row| row.len());
+        assert!(
+            rows.is_power_of_two() && self.data.iter().all(|row| row.len() == rows),
+            "Invalid gate: expected a square matrix with a power of two size, got {}x{}",
+            rows,
+            cols
+        );
+        rows.trailing_zeros() as usize
+    }
+
+    pub fn embed_gate(gate: &Matrix, target: usize, total_qubits: usize) -> Matrix {
+        // I_before ⊗ gate ⊗ I_after, gate acting on the qbits from target onwards
+        let gate_qubits = gate.gate_qubits();
+        assert!(
+            target + gate_qubits <= total_qubits,
+            "Gate targets fall outside of the register"
+        );
+
+        let before = Matrix::identity(2_usize.pow(target as u32));
+        let after = Matrix::identity(2_usize.pow((total_qubits - target - gate_qubits) as u32));
+        before.tensor(gate).tensor(&after)
+    }
+
+    pub fn tensor_pow(&self, n: usize) -> Matrix {
+        // self ⊗ ... ⊗ self (n times), the 1x1 identity for n = 0
+        (0..n).fold(Matrix::identity(1), |acc, _| acc.tensor(self))
+    }
+
+    pub fn tensor_with_order(&self, other: &Matrix, little_endian: bool) -> Matrix {
+        // Big endian (the `tensor` default): self holds the most significant qbits, so
+        // |a> tensor |b> = |ab>. Little endian puts self on the least significant qbits: |ba>
+        if little_endian {
+            other.tensor(self)
+        } else {
+            self.tensor(other)
+        }
+    }
+
+    pub fn apply_gate_at(&self, gate: &Matrix, targets: &[usize], n_qubits: usize) -> Matrix {
+        // Applies gate to the state vector self, the i-th target being the i-th (most
+        // significant first) qbit of the gate. Targets need not be adjacent nor ordered
+        let gate_size = gate.data.len();
+        assert!(self.is_vector(), "Gates can only be applied to a vector");
+        assert_eq!(
+            self.data.len(),
+            2_usize.pow(n_qubits as u32),
+            "Vector does not hold {} qbits",
+            n_qubits
+        );
+        assert!(
+            gate.size() == (gate_size, gate_size) && gate_size == 1 << targets.len(),
+            "Gate should be a square matrix acting on {} qbits",
+            targets.len()
+        );
+        assert!(
+            targets
+                .iter()
+                .enumerate()
+                .all(|(i, q)| *q < n_qubits && !targets[..i].contains(q)),
+            "Targets should be distinct and inside the register"
+        );
+
+        let masks: Vec<usize> = targets.iter().map(|q| 1 << (n_qubits - 1 - q)).collect();
+        let target_mask: usize = masks.iter().sum();
+        // Index in the register of the gate basis state k, on top of the spectator bits of base
+        let scatter = |base: usize, k: usize| {
+            masks.iter().enumerate().fold(base, |index, (i, mask)| {
+                if (k >> (targets.len() - 1 - i)) & 1 == 1 {
+                    index | mask
+                } else {
+                    index
+                }
+            })
+        };
+
+        let mut res = Matrix::zero(self.data.len(), 1);
+        for base in (0..self.data.len()).filter(|index| index & target_mask == 0) {
+            let indices: Vec<usize> = (0..gate_size).map(|k| scatter(base, k)).collect();
+            for (gate_row, row) in indices.iter().enumerate() {
+                res.data[*row][0] = indices
+                    .iter()
+                    .enumerate()
+                    .fold(c!(0), |acc, (gate_col, col)| {
+                        acc + gate.data[gate_row][gate_col] * self.data[*col][0]
+                    });
             }
         }
-        return norm.sqrt();
+
+        res
     }
 
+    pub fn norm(&self) -> f64 {
+        self.inner_product(self).a.sqrt()
+    }
+
+    pub fn approx_eq(&self, other: &Matrix, eps: f64) -> bool {
+        self.size() == other.size()
+            && self.data.iter().zip(&other.data).all(|(row, other_row)| {
+                row.iter()
+                    .zip(other_row)
+                    .all(|(a, b)| (a.a - b.a).abs() <= eps && (a.b - b.b).abs() <= eps)
+            })
+    }
+
+    pub fn is_close_to_identity(&self, eps: f64) -> bool {
+        let (rows, cols) = self.size();
+        rows == cols
+            && self.data.iter().enumerate().all(|(i, row)| {
+                row.iter().enumerate().all(|(j, v)| {
+                    let expected = if i == j { 1.0 } else { 0.0 };
+                    (v.a - expected).abs() <= eps && v.b.abs() <= eps
+                })
+            })
+    }
+
+    // Both checks allow every entry to be off by UNITARY_EPS to absorb rounding in
+    // gates built from 1/sqrt(2) or trigonometric entries
     pub fn is_unitary(&self) -> bool {
-        let adj = self.adjoint();
-        let id = Matrix::identity(self.data.len());
-        let res = self.clone() * adj;
-        res == id
+        let res = self.clone() * self.adjoint();
+        res.is_close_to_identity(UNITARY_EPS)
     }
 
     pub fn is_hermitian(&self) -> bool {
-        self.clone() == self.adjoint()
+        self.approx_eq(&self.adjoint(), UNITARY_EPS)
+    }
+
+    pub fn is_valid_density_matrix(&self, eps: f64) -> bool {
+        // Hermitian, unit trace and no eigenvalue below -eps
+        let (rows, cols) = self.size();
+        rows == cols
+            && self.approx_eq(&self.adjoint(), eps)
+            && (self.trace().a - 1.0).abs() <= eps
+            && self.is_positive_semidefinite(eps)
+    }
+
+    fn is_positive_semidefinite(&self, eps: f64) -> bool {
+        // Gaussian elimination of a Hermitian matrix: it is PSD iff no pivot is negative
+        // and every zero pivot comes with a zero row
+        let mut data = self.data.clone();
+        let size = data.len();
+        for k in 0..size {
+            let pivot = data[k][k].a;
+            if pivot < -eps {
+                return false;
+            }
+            if pivot <= eps {
+                if data[k][k + 1..].iter().any(|v| v.modulus() > eps) {
+                    return false;
+                }
+                continue;
+            }
+
+            let (upper, lower) = data.split_at_mut(k + 1);
+            let pivot_row = &upper[k];
+            for row in lower {
+                let factor = row[k] / c!(pivot);
+                for (v, p) in row[k + 1..].iter_mut().zip(&pivot_row[k + 1..]) {
+                    *v -= factor * *p;
+                }
+            }
+        }
+        true
     }
 
     pub fn is_vector(&self) -> bool {
@@ -206,6 +810,64 @@ impl Matrix {
         // (cols, rows)
         (self.data.len(), self.data[0].len())
     }
+
+    // Entries rendered with the C Display, right aligned in columns
+    pub fn to_string_grid(&self) -> String {
+        let cells: Vec<Vec<String>> = self
+            .data
+            .iter()
+            .map(|row| row.iter().map(|v| v.to_string()).collect())
+            .collect();
+        let widths: Vec<usize> = (0..self.data[0].len())
+            .map(|j| cells.iter().map(|row| row[j].len()).max().unwrap_or(0))
+            .collect();
+
+        cells
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .zip(&widths)
+                    .map(|(cell, width)| format!("{:>width$}", cell, width = width))
+                    .collect::<Vec<String>>()
+                    .join("  ")
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    pub fn assert_valid(&self) {
+        assert!(!self.data.is_empty(), "Invalid matrix: no rows");
+
+        let cols = self.data[0].len();
+        for (i, row) in self.data.iter().enumerate() {
+            assert_eq!(
+                row.len(),
+                cols,
+                "Invalid matrix: row {} has {} columns, expected {}",
+                i,
+                row.len(),
+                cols
+            );
+
+            for (j, v) in row.iter().enumerate() {
+                assert!(
+                    v.a.is_finite() && v.b.is_finite(),
+                    "Invalid matrix: entry ({}, {}) is not finite: {:?}",
+                    i,
+                    j,
+                    v
+                );
+            }
+        }
+    }
+
+    // Validates the invariants of freshly constructed matrices in debug builds only
+    fn checked(self) -> Matrix {
+        if cfg!(debug_assertions) {
+            self.assert_valid();
+        }
+        self
+    }
 }
 
 #[macro_export]
@@ -215,12 +877,42 @@ macro_rules! mat {
     };
 }
 
+// 1 / sqrt(n), the single place normalization factors are computed so they match exactly
+pub fn inv_sqrt(n: f64) -> C {
+    c!(1.0 / n.sqrt())
+}
+
+pub fn inv_sqrt_2() -> C {
+    inv_sqrt(2.0)
+}
+
 pub fn hadamard() -> Matrix {
     mat![
         c!(1), c!(1);
         c!(1), c!(-1);
     ]
-    .scalar_mul(c!(1.0 / 2.0_f64.sqrt()))
+    .scalar_mul(inv_sqrt_2())
+}
+
+pub fn pauli_x() -> Matrix {
+    mat![
+        c!(0), c!(1);
+        c!(1), c!(0);
+    ]
+}
+
+pub fn pauli_y() -> Matrix {
+    mat![
+        c!(0), c!(0, -1);
+        c!(0, 1), c!(0);
+    ]
+}
+
+pub fn pauli_z() -> Matrix {
+    mat![
+        c!(1), c!(0);
+        c!(0), c!(-1);
+    ]
 }
 
 pub fn cnot() -> Matrix {
@@ -232,139 +924,556 @@ pub fn cnot() -> Matrix {
     ]
 }
 
+pub fn cnot_on(control: usize, target: usize, n_qubits: usize) -> Matrix {
+    multi_controlled(&pauli_x(), &[control], target, n_qubits)
+}
+
+// R(phase) = diag(1, e^{i phase}): |0> is untouched and |1> picks up the phase,
+// the convention used by the QFT where R_k = phase_shift(2 pi / 2^k)
 pub fn phase_shift(phase: f64) -> Matrix {
     mat![
         c!(1), c!(0);
-        c!(0), c!(phase.cos(), phase.sin());
+        c!(0), c!(0.0, phase).exp();
     ]
 }
 
-pub fn unitary_modular(a: usize, n: usize) -> Matrix {
-    let nbit_size = min_bit_size(n as u32);
-    let mbit_size = nbit_size * 2;
-    let qbit_size = nbit_size + mbit_size;
+// Conjugate convention diag(1, e^{-i phase}), the inverse (adjoint) of phase_shift(phase)
+pub fn phase_shift_neg(phase: f64) -> Matrix {
+    phase_shift(-phase)
+}
+
+pub fn swap() -> Matrix {
+    mat![
+        c!(1), c!(0), c!(0), c!(0);
+        c!(0), c!(0), c!(1), c!(0);
+        c!(0), c!(1), c!(0), c!(0);
+        c!(0), c!(0), c!(0), c!(1);
+    ]
+}
+
+pub fn toffoli() -> Matrix {
+    // Flips qbit 2 iff qbits 0 and 1 are both 1
+    multi_controlled(&pauli_x(), &[0, 1], 2, 3)
+}
+
+// Rotations exp(-i theta P / 2) around the Pauli axis P
+pub fn rx(theta: f64) -> Matrix {
+    let (cos, sin) = ((theta / 2.0).cos(), (theta / 2.0).sin());
+    mat![
+        c!(cos), c!(0.0, -sin);
+        c!(0.0, -sin), c!(cos);
+    ]
+}
+
+pub fn ry(theta: f64) -> Matrix {
+    let (cos, sin) = ((theta / 2.0).cos(), (theta / 2.0).sin());
+    mat![
+        c!(cos), c!(-sin);
+        c!(sin), c!(cos);
+    ]
+}
+
+pub fn rz(theta: f64) -> Matrix {
+    mat![
+        c!(0.0, -theta / 2.0).exp(), c!(0);
+        c!(0), c!(0.0, theta / 2.0).exp();
+    ]
+}
+
+pub fn controlled(gate: &Matrix) -> Matrix {
+    // Identity with gate as bottom-right block, the first qbit being the control
+    let target_len = gate.gate_qubits();
+    multi_controlled(gate, &[0], 1, 1 + target_len)
+}
 
-    let m_size = (2 as u32).clone().pow(qbit_size.clone() as u32) as usize;
-    let n_bit_represenation = (2 as u32).clone().pow(nbit_size.clone() as u32);
-    let m_bit_represenation = (2 as u32).clone().pow(mbit_size.clone() as u32);
+pub fn multi_controlled(
+    gate: &Matrix,
+    controls: &[usize],
+    target_start: usize,
+    n_qubits: usize,
+) -> Matrix {
+    // Applies gate to the qbits target_start.. only if every control qbit is 1,
+    // qbit 0 being the most significant one
+    let gate_size = gate.data.len();
+    let target_len = gate_size.trailing_zeros() as usize;
+    assert!(
+        gate.size() == (gate_size, gate_size) && gate_size.is_power_of_two(),
+        "Gate should be a square matrix of size power of two"
+    );
+    assert!(
+        target_start + target_len <= n_qubits,
+        "Gate targets fall outside of the register"
+    );
+    assert!(
+        controls
+            .iter()
+            .all(|q| *q < n_qubits && (*q < target_start || *q >= target_start + target_len)),
+        "Controls should be distinct from the targets and inside the register"
+    );
+
+    let size = 2_usize.pow(n_qubits as u32);
+    let target_shift = n_qubits - target_start - target_len;
+    let target_mask = (gate_size - 1) << target_shift;
+    let bit = |index: usize, qubit: usize| (index >> (n_qubits - 1 - qubit)) & 1;
 
-    let mut matrix = Matrix::zero_sq(m_size);
+    let mut matrix = Matrix::zero_sq(size);
+    for col in 0..size {
+        if !controls.iter().all(|q| bit(col, *q) == 1) {
+            matrix.data[col][col] = c!(1);
+            continue;
+        }
 
-    for i in 0..m_bit_represenation {
-        let f = mod_power(a as u32, i, n as u32) as usize;
-        let sq_factor = (i * n_bit_represenation) as usize;
-        matrix = matrix.set( sq_factor + f, sq_factor, c!(1));
+        let rest = col & !target_mask;
+        let gate_col = (col & target_mask) >> target_shift;
+        for gate_row in 0..gate_size {
+            let row = rest | (gate_row << target_shift);
+            matrix.data[row][col] = gate.data[gate_row][gate_col];
+        }
     }
 
     matrix
 }
 
+pub fn hadamard_layer(n_qubits: usize) -> Matrix {
+    // H^n has entries (-1)^(i·j) / sqrt(2^n), with i·j the bitwise dot product
+    let size = 2_usize.pow(n_qubits as u32);
+    let amplitude = inv_sqrt(size as f64);
+    let data: Vec<Vec<C>> = (0..size)
+        .map(|i| {
+            (0..size)
+                .map(|j| match (i & j).count_ones() % 2 {
+                    0 => amplitude,
+                    _ => -amplitude,
+                })
+                .collect()
+        })
+        .collect();
+    Matrix::new(data)
+}
+
+pub fn uniform_superposition(n_qubits: usize) -> Matrix {
+    // H^n |0...0>, every amplitude equals 1 / sqrt(2^n)
+    let size = 2_usize.pow(n_qubits as u32);
+    let amplitude = inv_sqrt(size as f64);
+    Matrix::new(vec![vec![amplitude]; size])
+}
+
+pub fn unitary_modular(a: usize, n: usize) -> Matrix {
+    Matrix::permutation(&modular_mapping(a, n)).expect("xor with f(x) is its own inverse")
+}
+
+// Basis state each basis state of the Shor register is sent to by unitary_modular
+pub(crate) fn modular_mapping(a: usize, n: usize) -> Vec<usize> {
+    let nbit_size = min_bit_size(n as u32);
+    let mbit_size = nbit_size * 2;
+    let qbit_size = nbit_size + mbit_size;
+
+    let m_size = 1usize << qbit_size;
+    let n_bit_represenation = 1usize << nbit_size;
+
+    // |x>|y> -> |x>|y xor a^x mod n>, with x on the m bits and y on the n bits
+    (0..m_size)
+        .map(|i| {
+            let x = i / n_bit_represenation;
+            let f = mod_power(a as u32, x as u32, n as u32) as usize;
+            i ^ f
+        })
+        .collect::<Vec<usize>>()
+}
+
 pub fn quantum_fourier(n: usize) -> Matrix {
     let size = (2 as u32).clone().pow(n.clone() as u32) as usize;
     let mut matrix = Matrix::zero_sq(size);
 
-    let base = c!((size as f64).powf(-0.5));
+    // Entries omega^(i * j) / sqrt(N) with omega = e^(2 pi i / N)
+    let base = inv_sqrt(size as f64);
     for i in 0..size {
         for j in 0..size {
-            let v = c!(0.0, 1.0).pow(i * j);
-            matrix = matrix.set(i, j, base * v);
+            let angle = 2.0 * core::f64::consts::PI * ((i * j) % size) as f64 / size as f64;
+            matrix.set_mut(i, j, base * c!(0.0, angle).exp());
         }
     }
 
     matrix
-
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::assert_matrix_approx_eq;
+
     use super::*;
 
     #[test]
-    fn test_matrix_macro() {
-        let m = mat!(c!(1), c!(2); c!(3), c!(4));
-        assert_eq!(m.data, vec![vec![c!(1), c!(2)], vec![c!(3), c!(4)]]);
+    fn test_matrix_macro() {
+        let m = mat!(c!(1), c!(2); c!(3), c!(4));
+        assert_eq!(m.data, vec![vec![c!(1), c!(2)], vec![c!(3), c!(4)]]);
+    }
+
+    #[test]
+    fn test_matrix_new() {
+        let m = Matrix::new(vec![vec![c!(1), c!(2)], vec![c!(3), c!(4)]]);
+        assert_eq!(m.data, vec![vec![c!(1), c!(2)], vec![c!(3), c!(4)]]);
+    }
+
+    #[test]
+    fn test_matrix_from_basis_string() {
+        let m = Matrix::from_basis_string("00").unwrap();
+        assert_eq!(m, mat!(c!(1); c!(0); c!(0); c!(0)));
+
+        let m = Matrix::from_basis_string("11").unwrap();
+        assert_eq!(m, mat!(c!(0); c!(0); c!(0); c!(1)));
+
+        let m = Matrix::from_basis_string("011").unwrap();
+        assert_eq!(m.size(), (8, 1));
+        assert_eq!(m.data[3][0], c!(1));
+
+        assert!(matches!(
+            Matrix::from_basis_string("0a"),
+            Err(MatrixError::InvalidBasisString(_))
+        ));
+    }
+
+    #[test]
+    fn test_matrix_identity() {
+        let m = Matrix::identity(3);
+        assert_eq!(
+            m.data,
+            vec![
+                vec![c!(1), c!(0), c!(0)],
+                vec![c!(0), c!(1), c!(0)],
+                vec![c!(0), c!(0), c!(1)]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_matrix_transpose() {
+        let m = mat!(c!(1), c!(2); c!(3), c!(4));
+        let m2 = mat!(c!(1), c!(3); c!(2), c!(4));
+        let t = m.transpose();
+        assert_eq!(t, m2);
+    }
+
+    #[test]
+    fn test_matrix_add() {
+        let m1 = mat!(c!(1), c!(2); c!(3), c!(4));
+        let m2 = mat!(c!(5), c!(6); c!(7), c!(8));
+
+        let m3 = m1 + m2;
+
+        let res = mat!(
+            c!(6), c!(8);
+            c!(10), c!(12);
+        );
+        assert_eq!(m3, res);
+    }
+
+    #[test]
+    fn test_matrix_scalar_mul() {
+        let m = mat!(c!(1), c!(2); c!(3), c!(4));
+        let m2 = m.scalar_mul(c!(2));
+
+        let res = mat!(c!(2), c!(4); c!(6), c!(8));
+        assert_eq!(m2, res);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_matrix_negative_inverse() {
+        let m = mat!(c!(1), c!(2); c!(3), c!(4));
+        let m2 = m.negative_inverse();
+
+        let res = mat!(c!(-1), c!(-2); c!(-3), c!(-4));
+        assert_eq!(m2, res);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_matrix_neg() {
+        let m = mat!(c!(1), c!(2, -1); c!(3), c!(4));
+        assert_eq!(-&m, m.negative_inverse());
+        assert_eq!(-m.clone(), mat!(c!(-1), c!(-2, 1); c!(-3), c!(-4)));
+        assert_eq!(-(-m.clone()), m);
+    }
+
+    #[test]
+    fn test_matrix_sub() {
+        let m1 = mat!(c!(5), c!(6); c!(7), c!(8));
+        let m2 = mat!(c!(1), c!(2); c!(3), c!(4));
+        assert_eq!(m1 - m2, mat!(c!(4), c!(4); c!(4), c!(4)));
+
+        let v1 = mat!(c!(1); c!(0, 1); c!(2));
+        let v2 = mat!(c!(1); c!(0); c!(1));
+        assert_eq!(v1 - v2, mat!(c!(0); c!(0, 1); c!(1)));
+    }
+
+    #[test]
+    fn test_matrix_mul() {
+        let m1 = mat!(c!(1), c!(2); c!(3), c!(4));
+        let m2 = mat!(c!(5), c!(6); c!(7), c!(8));
+
+        let m3 = m1 * m2;
+
+        let res = mat!(
+            c!(19), c!(22);
+            c!(43), c!(50);
+        );
+        assert_eq!(m3, res);
+    }
+
+    #[test]
+    fn test_matrix_try_multiply() {
+        let m = mat!(c!(1), c!(2); c!(3), c!(4));
+        let v = mat!(c!(1); c!(1));
+
+        assert_eq!(m.try_multiply(&v).unwrap(), mat!(c!(3); c!(7)));
+        assert!(matches!(
+            v.try_multiply(&m),
+            Err(MatrixError::DimensionMismatch((2, 1), (2, 2)))
+        ));
+        assert!(matches!(
+            m.try_multiply(&Matrix::new(vec![])),
+            Err(MatrixError::Empty)
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "Dimension mismatch between a 2x1 and a 2x1 matrix")]
+    fn test_matrix_multiply_mismatch() {
+        let v = mat!(c!(1); c!(1));
+        let _ = v.clone() * v;
+    }
+
+    #[test]
+    fn test_matrix_try_tensor() {
+        let v = mat!(c!(1); c!(0));
+        assert_eq!(v.try_tensor(&v).unwrap(), v.tensor(&v));
+        assert!(matches!(
+            Matrix::new(vec![vec![]]).try_tensor(&v),
+            Err(MatrixError::Empty)
+        ));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_matrix_dot() {
+        let m1 = mat!(c!(1), c!(2); c!(3), c!(4));
+        let m2 = mat!(c!(5), c!(6); c!(7), c!(8));
+
+        let res = m1.dot(m2);
+        assert_eq!(res, c!(70));
+    }
+
+    #[test]
+    fn test_matrix_outer_product() {
+        let plus = hadamard().multiply(&Matrix::from_basis_string("0").unwrap());
+        let half = c!(0.5);
+        assert_eq!(plus.outer_product(&plus), mat!(half, half; half, half));
+
+        let zero = Matrix::from_basis_string("0").unwrap();
+        let one = Matrix::from_basis_string("1").unwrap();
+        assert_eq!(zero.outer_product(&zero), mat!(c!(1), c!(0); c!(0), c!(0)));
+        assert_eq!(zero.outer_product(&one), mat!(c!(0), c!(1); c!(0), c!(0)));
+
+        let v = mat!(c!(0, 1); c!(0));
+        assert_eq!(v.outer_product(&v).get(0, 0), c!(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Outer product requires two column vectors")]
+    fn test_matrix_outer_product_non_vector() {
+        hadamard().outer_product(&Matrix::from_basis_string("0").unwrap());
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_matrix_inner_product() {
+        let v = mat!(c!(1, 1); c!(0, 2));
+
+        assert_eq!(v.dot(v.clone()), c!(-4, 2));
+        assert_eq!(v.inner_product(&v), c!(6));
+        assert!(f64_equal(v.norm(), 6.0_f64.sqrt()));
+
+        let w = mat!(c!(0, 1); c!(1));
+        assert_eq!(v.inner_product(&w), v.adjoint().multiply(&w).data[0][0]);
+        assert_eq!(w.inner_product(&v), v.inner_product(&w).conjugate());
+    }
+
+    #[test]
+    fn test_matrix_iter_nonzero() {
+        let mut v = Matrix::zero(8, 1);
+        v.data[2][0] = c!(0.5);
+        v.data[5][0] = c!(0.0, -0.5);
+        v.data[7][0] = c!(1e-12);
+
+        let entries: Vec<(usize, usize, C)> = v.iter_nonzero(1e-9).collect();
+        assert_eq!(entries, vec![(2, 0, c!(0.5)), (5, 0, c!(0.0, -0.5))]);
+        assert_eq!(Matrix::zero_sq(4).iter_nonzero(1e-9).count(), 0);
+    }
+
+    #[test]
+    fn test_matrix_get_set() {
+        let m = mat!(c!(1), c!(2); c!(3), c!(0, 4));
+        assert_eq!(m.get(1, 1), c!(0, 4));
+        assert_eq!(m.get(0, 1), c!(2));
+
+        let copied = m.set(1, 0, c!(-1)).set(0, 0, c!(5));
+        let mut in_place = m.clone();
+        in_place.set_mut(1, 0, c!(-1));
+        in_place.set_mut(0, 0, c!(5));
+
+        assert_eq!(copied, in_place);
+        assert_eq!(in_place, mat!(c!(5), c!(2); c!(-1), c!(0, 4)));
+        assert_eq!(m.get(0, 0), c!(1));
+    }
+
+    #[test]
+    fn test_matrix_row_col_submatrix() {
+        let mut m = Matrix::zero_sq(4);
+        for i in 0..4 {
+            for j in 0..4 {
+                m.set_mut(i, j, c!((4 * i + j) as f64));
+            }
+        }
+
+        assert_eq!(m.row(1), vec![c!(4), c!(5), c!(6), c!(7)]);
+        assert_eq!(m.col(2), vec![c!(2), c!(6), c!(10), c!(14)]);
+        assert_eq!(m.submatrix(1..3, 2..4), mat!(c!(6), c!(7); c!(10), c!(11)));
+        assert_eq!(m.submatrix(0..4, 0..4), m);
+    }
+
+    #[test]
+    fn test_matrix_stack() {
+        let a = mat!(c!(1), c!(2); c!(3), c!(4));
+        let b = mat!(c!(5), c!(6); c!(7), c!(8));
+
+        assert_eq!(
+            a.hstack(&b),
+            mat!(c!(1), c!(2), c!(5), c!(6); c!(3), c!(4), c!(7), c!(8))
+        );
+        assert_eq!(
+            a.vstack(&b),
+            mat!(c!(1), c!(2); c!(3), c!(4); c!(5), c!(6); c!(7), c!(8))
+        );
+        assert_eq!(a.vstack(&b).submatrix(2..4, 0..2), b);
+    }
+
+    #[test]
+    #[should_panic(expected = "hstack requires equal row counts")]
+    fn test_matrix_hstack_mismatch() {
+        Matrix::identity(2).hstack(&Matrix::identity(4));
     }
 
     #[test]
-    fn test_matrix_new() {
-        let m = Matrix::new(vec![vec![c!(1), c!(2)], vec![c!(3), c!(4)]]);
-        assert_eq!(m.data, vec![vec![c!(1), c!(2)], vec![c!(3), c!(4)]]);
+    #[should_panic(expected = "vstack requires equal column counts")]
+    fn test_matrix_vstack_mismatch() {
+        Matrix::identity(2).vstack(&Matrix::zero(2, 1));
     }
 
     #[test]
-    fn test_matrix_identity() {
-        let m = Matrix::identity(3);
+    fn test_matrix_to_string_grid() {
+        assert_eq!(Matrix::identity(2).to_string_grid(), "1  0\n0  1");
         assert_eq!(
-            m.data,
-            vec![
-                vec![c!(1), c!(0), c!(0)],
-                vec![c!(0), c!(1), c!(0)],
-                vec![c!(0), c!(0), c!(1)]
-            ]
+            mat!(c!(1), c!(0, -1); c!(-0.5), c!(2, 3)).to_string_grid(),
+            "   1    -i\n-0.5  2+3i"
+        );
+        assert_eq!(
+            Matrix::from_basis_string("1").unwrap().to_string_grid(),
+            "0\n1"
         );
     }
 
     #[test]
-    fn test_matrix_transpose() {
-        let m = mat!(c!(1), c!(2); c!(3), c!(4));
-        let m2 = mat!(c!(1), c!(3); c!(2), c!(4));
-        let t = m.transpose();
-        assert_eq!(t, m2);
+    fn test_matrix_renormalized() {
+        let mut state = uniform_superposition(2);
+        assert!(state.is_normalized(1e-9));
+
+        state.set_mut(1, 0, c!(0));
+        state.set_mut(2, 0, c!(0));
+        assert!(!state.is_normalized(1e-9));
+
+        let state = state.renormalized();
+        assert!(state.is_normalized(1e-9));
+        let total: f64 = state.col(0).iter().map(|v| v.modulus().powi(2)).sum();
+        assert!(f64_equal(total, 1.0));
     }
 
     #[test]
-    fn test_matrix_add() {
-        let m1 = mat!(c!(1), c!(2); c!(3), c!(4));
-        let m2 = mat!(c!(5), c!(6); c!(7), c!(8));
-
-        let m3 = m1 + m2;
+    #[should_panic(expected = "Cannot renormalize")]
+    fn test_matrix_renormalized_zero() {
+        Matrix::zero(2, 1).renormalized();
+    }
 
-        let res = mat!(
-            c!(6), c!(8);
-            c!(10), c!(12);
+    #[test]
+    fn test_matrix_trace() {
+        assert_eq!(Matrix::identity(4).trace(), c!(4));
+        assert_eq!(mat!(c!(1, 2), c!(5); c!(7), c!(3, -1)).trace(), c!(4, 1));
+        assert_eq!(
+            mat!(c!(2), c!(9), c!(9); c!(9), c!(0, -3), c!(9); c!(9), c!(9), c!(-0.5)).trace(),
+            c!(1.5, -3.0)
         );
-        assert_eq!(m3, res);
     }
 
     #[test]
-    fn test_matrix_scalar_mul() {
-        let m = mat!(c!(1), c!(2); c!(3), c!(4));
-        let m2 = m.scalar_mul(c!(2));
-
-        let res = mat!(c!(2), c!(4); c!(6), c!(8));
-        assert_eq!(m2, res);
+    #[should_panic(expected = "Trace requires a square matrix")]
+    fn test_matrix_trace_non_square() {
+        Matrix::zero(2, 1).trace();
     }
 
     #[test]
-    fn test_matrix_negative_inverse() {
-        let m = mat!(c!(1), c!(2); c!(3), c!(4));
-        let m2 = m.negative_inverse();
-
-        let res = mat!(c!(-1), c!(-2); c!(-3), c!(-4));
-        assert_eq!(m2, res);
+    #[allow(deprecated)]
+    fn test_matrix_negative_inverse_zero() {
+        let res = Matrix::zero_sq(2).negative_inverse();
+        assert_eq!(res, Matrix::zero_sq(2));
+        for v in res.data.iter().flatten() {
+            assert_eq!(v.to_string(), "0");
+        }
     }
 
     #[test]
-    fn test_matrix_mul() {
-        let m1 = mat!(c!(1), c!(2); c!(3), c!(4));
-        let m2 = mat!(c!(5), c!(6); c!(7), c!(8));
+    fn test_matrix_inverse() {
+        let m = mat!(c!(1, 1), c!(2); c!(0), c!(0, -1));
+        assert_eq!(&m * &m.inverse().unwrap(), Matrix::identity(2));
 
-        let m3 = m1 * m2;
+        let m = mat!(c!(0), c!(1), c!(2); c!(1), c!(0), c!(3); c!(4), c!(-3), c!(8));
+        let inv = m.inverse().unwrap();
+        assert_eq!(&m * &inv, Matrix::identity(3));
+        assert_eq!(
+            inv,
+            mat!(c!(-4.5), c!(7), c!(-1.5); c!(-2), c!(4), c!(-1); c!(1.5), c!(-2), c!(0.5))
+        );
 
-        let res = mat!(
-            c!(19), c!(22);
-            c!(43), c!(50);
+        let singular = mat!(c!(1), c!(2); c!(2), c!(4));
+        assert_eq!(singular.inverse(), None);
+    }
+
+    #[test]
+    fn test_matrix_determinant() {
+        assert_eq!(mat!(c!(0, 3)).determinant(), c!(0, 3));
+        assert_eq!(mat!(c!(1), c!(2); c!(3), c!(4)).determinant(), c!(-2));
+        assert_eq!(
+            mat!(c!(0), c!(1), c!(2); c!(1), c!(0), c!(3); c!(4), c!(-3), c!(8)).determinant(),
+            c!(-2)
         );
-        assert_eq!(m3, res);
+        assert_eq!(mat!(c!(1), c!(2); c!(2), c!(4)).determinant(), c!(0));
+        assert_eq!(pauli_y().determinant(), c!(-1));
+
+        for n in 1..5 {
+            assert_eq!(Matrix::identity(n).determinant(), c!(1));
+        }
     }
 
     #[test]
-    fn test_matrix_dot() {
-        let m1 = mat!(c!(1), c!(2); c!(3), c!(4));
-        let m2 = mat!(c!(5), c!(6); c!(7), c!(8));
+    fn test_matrix_hilbert_schmidt() {
+        let x = mat!(c!(0), c!(1); c!(1), c!(0));
+        assert_eq!(x.hilbert_schmidt(&x), c!(2));
+        assert_eq!(x.hilbert_schmidt_normalized(&x), c!(1));
 
-        let res = m1.dot(m2);
-        assert_eq!(res, c!(70));
+        let id = Matrix::identity(2);
+        assert_eq!(x.hilbert_schmidt(&id), c!(0));
+
+        let m = mat!(c!(0, 1), c!(0); c!(0), c!(1));
+        assert_eq!(m.hilbert_schmidt(&id), c!(1, -1));
     }
 
     #[test]
@@ -374,6 +1483,15 @@ mod tests {
         assert_eq!(res, 14.0_f64.sqrt());
     }
 
+    #[test]
+    fn test_matrix_normalized() {
+        let m = mat!(c!(3); c!(4));
+        assert_eq!(m.normalized().unwrap(), mat!(c!(0.6); c!(0.8)));
+
+        let tiny = mat!(c!(1e-300); c!(0));
+        assert!(matches!(tiny.normalized(), Err(MatrixError::ZeroNorm(_))));
+    }
+
     #[test]
     fn test_matrix_conjugate() {
         let m = mat!(c!(1, 1), c!(0, 2); c!(3), c!(4, -1));
@@ -396,6 +1514,51 @@ mod tests {
         assert!(!m2.is_hermitian());
     }
 
+    #[test]
+    fn test_matrix_tolerant_equality() {
+        // 1/sqrt(2)^2 is not exactly 1/2 in floating point
+        assert_eq!(hadamard() * hadamard(), Matrix::identity(2));
+        assert_eq!(
+            phase_shift(std::f64::consts::FRAC_PI_2) * phase_shift(std::f64::consts::FRAC_PI_2),
+            pauli_z()
+        );
+    }
+
+    #[test]
+    fn test_matrix_is_close_to_identity() {
+        assert!((hadamard() * hadamard()).is_close_to_identity(1e-9));
+        assert!(Matrix::identity(4).is_close_to_identity(0.0));
+        assert!(!cnot().is_close_to_identity(1e-9));
+
+        let phased = Matrix::identity(2).scalar_mul(c!(0, 1));
+        assert!(!phased.is_close_to_identity(1e-9));
+        let vector = Matrix::zero(2, 1).set(0, 0, c!(1));
+        assert!(!vector.is_close_to_identity(1e-9));
+    }
+
+    #[test]
+    fn test_matrix_is_valid_density_matrix() {
+        let zero = Matrix::from_basis_string("0").unwrap();
+        assert!((zero.clone() * zero.adjoint()).is_valid_density_matrix(1e-9));
+        assert!(Matrix::identity(2)
+            .scalar_mul(c!(0.5))
+            .is_valid_density_matrix(1e-9));
+
+        let plus = uniform_superposition(2);
+        assert!((plus.clone() * plus.adjoint()).is_valid_density_matrix(1e-9));
+
+        // Hermitian with unit trace, but eigenvalues 1.1 and -0.1
+        let negative = mat!(c!(0.5), c!(0.6); c!(0.6), c!(0.5));
+        assert!(!negative.is_valid_density_matrix(1e-9));
+
+        let zero_pivot = mat!(c!(0), c!(0.5); c!(0.5), c!(1));
+        assert!(!zero_pivot.is_valid_density_matrix(1e-9));
+
+        let not_hermitian = mat!(c!(0.5), c!(0.1); c!(0), c!(0.5));
+        assert!(!not_hermitian.is_valid_density_matrix(1e-9));
+        assert!(!Matrix::identity(2).is_valid_density_matrix(1e-9));
+    }
+
     #[test]
     fn test_matrix_is_hermitian() {
         let m = mat!(
@@ -408,6 +1571,20 @@ mod tests {
 
         let m2 = mat!(c!(5), c!(6); c!(7), c!(8));
         assert!(!m2.is_hermitian());
+
+        assert!(hadamard().is_hermitian());
+        assert!(mat!(c!(1), c!(2.0 + 1e-12); c!(2), c!(1)).is_hermitian());
+    }
+
+    #[test]
+    fn test_matrix_is_unitary_with_rounding() {
+        assert!(hadamard().is_unitary());
+        assert!(cnot().is_unitary());
+        assert!(phase_shift(1.0).is_unitary());
+        assert!(phase_shift(core::f64::consts::PI / 3.0).is_unitary());
+
+        let m = mat!(c!(1), c!(1); c!(1), c!(-1)).scalar_mul(c!(0.7));
+        assert!(!m.is_unitary());
     }
 
     #[test]
@@ -476,6 +1653,97 @@ mod tests {
         assert_eq!(m4.tensor(&m5), res2);
     }
 
+    // Entry by entry tensor product, without the block-wise layout or the short-circuits
+    fn tensor_reference(a: &Matrix, b: &Matrix) -> Matrix {
+        let mut res = Matrix::zero(a.size().0 * b.size().0, a.size().1 * b.size().1);
+        for i in 0..res.size().0 {
+            for j in 0..res.size().1 {
+                res.data[i][j] =
+                    a.data[i / b.size().0][j / b.size().1] * b.data[i % b.size().0][j % b.size().1];
+            }
+        }
+        res
+    }
+
+    #[test]
+    fn test_matrix_tensor_matches_reference() {
+        let m4 = mat!(c!(1, 2), c!(0); c!(1, 10), c!(1.4, 1.5));
+        let m5 = mat!(
+            c!(1), c!(2), c!(3);
+            c!(4), c!(5.1, -1.1), c!(4, 0);
+            c!(4), c!(3, -1), c!(1.3);
+            c!(1, 10), c!(1), c!(2);
+        );
+
+        // Bit for bit the same entries
+        let fast = m4.tensor(&m5);
+        let reference = tensor_reference(&m4, &m5);
+        assert_eq!(fast.size(), (8, 6));
+        for (row, reference_row) in fast.data.iter().zip(&reference.data) {
+            for (v, reference_v) in row.iter().zip(reference_row) {
+                assert!(v.exact_eq(reference_v));
+            }
+        }
+    }
+
+    #[test]
+    fn test_matrix_tensor_identity_short_circuit() {
+        // Bitwise equality of all entries, optionally treating -0.0 and 0.0 alike
+        let same_bits = |a: &Matrix, b: &Matrix, ignore_zero_sign: bool| {
+            a.size() == b.size()
+                && a.data
+                    .iter()
+                    .flatten()
+                    .zip(b.data.iter().flatten())
+                    .all(|(x, y)| {
+                        if ignore_zero_sign {
+                            x.normalize_zeros().exact_eq(&y.normalize_zeros())
+                        } else {
+                            x.exact_eq(y)
+                        }
+                    })
+        };
+
+        let one = mat!(c!(1));
+        let m = mat!(c!(1, 2), c!(-3); c!(0, -1), c!(4.5));
+        let signed_zeros = mat!(c!(-0.0, 0.0), c!(0.0, -0.0); c!(-0.0, -0.0), c!(-2.5, 0.1));
+        let v = mat!(c!(0.5); c!(0.0, -0.5));
+
+        for x in [&m, &signed_zeros, &v] {
+            assert!(same_bits(&x.tensor(&one), x, false));
+            assert!(same_bits(&one.tensor(x), x, false));
+
+            for id in [Matrix::identity(2), Matrix::identity(4)] {
+                assert!(same_bits(&id.tensor(x), &tensor_reference(&id, x), true));
+                assert!(same_bits(&x.tensor(&id), &tensor_reference(x, &id), true));
+            }
+        }
+
+        // The zero blocks of the fast path are literal zeros, 0 * -3 gives -0.0 in the general one
+        assert!(Matrix::identity(2).tensor(&m).data[2][1].exact_eq(&c!(0)));
+        assert!(tensor_reference(&Matrix::identity(2), &m).data[2][1].exact_eq(&c!(-0.0, 0.0)));
+
+        // Close to, but not exactly, the identity takes the general path, bit for bit
+        let almost = Matrix::identity(2).set(0, 1, c!(1e-12));
+        let general = almost.tensor(&m);
+        assert!(same_bits(&general, &tensor_reference(&almost, &m), false));
+        assert!(general.data[2][1].exact_eq(&c!(-0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_matrix_tensor_with_order() {
+        let zero = mat!(c!(1); c!(0));
+        let one = mat!(c!(0); c!(1));
+
+        let big = zero.tensor_with_order(&one, false);
+        let little = zero.tensor_with_order(&one, true);
+
+        assert_eq!(big, zero.tensor(&one));
+        assert_eq!(big, Matrix::from_basis_string("01").unwrap());
+        assert_eq!(little, Matrix::from_basis_string("10").unwrap());
+        assert_ne!(big, little);
+    }
+
     #[test]
     fn test_matrix_is_vector() {
         let m = mat!(c!(1), c!(2), c!(3));
@@ -486,6 +1754,75 @@ mod tests {
         assert!(!m2.is_vector());
     }
 
+    #[test]
+    fn test_matrix_assert_valid() {
+        mat!(c!(1), c!(0); c!(0), c!(1)).assert_valid();
+        hadamard().tensor(&hadamard()).assert_valid();
+    }
+
+    #[test]
+    #[should_panic(expected = "entry (1, 0) is not finite")]
+    fn test_matrix_assert_valid_nan() {
+        mat!(c!(1); c!(f64::NAN)).assert_valid();
+    }
+
+    #[test]
+    #[should_panic(expected = "row 1 has 1 columns")]
+    fn test_matrix_assert_valid_ragged() {
+        Matrix::new(vec![vec![c!(1), c!(0)], vec![c!(1)]]).assert_valid();
+    }
+
+    #[test]
+    #[should_panic(expected = "not finite")]
+    fn test_matrix_multiply_catches_nan() {
+        let m = mat!(c!(f64::INFINITY), c!(0); c!(0), c!(1));
+        let _ = m.clone() * mat!(c!(0); c!(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "not finite")]
+    fn test_matrix_multiply_catches_nan_from_zero_entry() {
+        let m = mat!(c!(0), c!(1));
+        let _ = m * mat!(c!(f64::INFINITY); c!(1));
+    }
+
+    #[test]
+    fn test_matrix_multiply_skipping_zeros_is_exact() {
+        let dense = |a: &Matrix, b: &Matrix| {
+            let mut res = Matrix::zero(a.data.len(), b.data[0].len());
+            for i in 0..a.data.len() {
+                for j in 0..b.data[0].len() {
+                    for k in 0..b.data.len() {
+                        res.data[i][j] += a.data[i][k] * b.data[k][j];
+                    }
+                }
+            }
+            res
+        };
+
+        let signed = mat!(
+            c!(-0.0, 0.0), c!(-3), c!(0.0, -0.0);
+            c!(0), c!(-0.0, -0.0), c!(2, -1);
+            c!(1e-300), c!(0), c!(-1e-300)
+        );
+        let cases = vec![
+            (hadamard_layer(3), quantum_fourier(3)),
+            (unitary_modular(2, 3), unitary_modular(2, 3).adjoint()),
+            (signed.clone(), signed.adjoint()),
+            (signed.clone(), mat!(c!(0); c!(-0.0); c!(1))),
+        ];
+
+        for (a, b) in cases {
+            let (res, expected) = (a.clone() * b.clone(), dense(&a, &b));
+            assert!(res
+                .data
+                .iter()
+                .flatten()
+                .zip(expected.data.iter().flatten())
+                .all(|(x, y)| x.exact_eq(y)));
+        }
+    }
+
     #[test]
     fn test_arb_matrix_mult() {
         let vec = mat!(c!(5); c!(0); c!(5); c!(0); c!(5); c!(0); c!(5); c!(0));
@@ -502,6 +1839,132 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_inv_sqrt() {
+        let h = hadamard();
+        assert_eq!(h.data[0][0].a, inv_sqrt_2().a);
+        assert_eq!(h.data[1][1].a, -inv_sqrt_2().a);
+        assert_eq!(inv_sqrt_2().b, 0.0);
+
+        assert_eq!(inv_sqrt(4.0), c!(0.5));
+        assert_eq!(uniform_superposition(1).data[0][0].a, inv_sqrt_2().a);
+    }
+
+    #[test]
+    fn test_phase_shift() {
+        let half = std::f64::consts::FRAC_1_SQRT_2;
+        let p = std::f64::consts::FRAC_PI_4;
+
+        assert_eq!(phase_shift(p), mat!(c!(1), c!(0); c!(0), c!(half, half)));
+        assert_eq!(
+            phase_shift_neg(p),
+            mat!(c!(1), c!(0); c!(0), c!(half, -half))
+        );
+        assert_eq!(phase_shift(std::f64::consts::PI), pauli_z());
+
+        assert!((phase_shift(p) * phase_shift_neg(p)).is_close_to_identity(1e-9));
+        assert_eq!(phase_shift_neg(p), phase_shift(p).adjoint());
+    }
+
+    #[test]
+    fn test_multi_controlled() {
+        let x = mat!(c!(0), c!(1); c!(1), c!(0));
+
+        assert_eq!(multi_controlled(&x, &[0], 1, 2), cnot());
+        assert_eq!(multi_controlled(&x, &[], 0, 1), x);
+
+        let mut toffoli = Matrix::identity(8);
+        toffoli.data.swap(6, 7);
+        assert_eq!(multi_controlled(&x, &[0, 1], 2, 3), toffoli);
+
+        // Control below the target: flips qbit 0 when qbit 1 is set
+        let reversed = multi_controlled(&x, &[1], 0, 2);
+        assert_eq!(
+            reversed * Matrix::from_basis_string("01").unwrap(),
+            Matrix::from_basis_string("11").unwrap()
+        );
+
+        let ccc_x = multi_controlled(&x, &[0, 1, 2], 3, 4);
+        for i in 0..16 {
+            let expected = match i {
+                14 => 15,
+                15 => 14,
+                _ => i,
+            };
+            assert_eq!(ccc_x.data[expected][i], c!(1));
+        }
+        assert!(ccc_x.is_unitary());
+
+        let h = multi_controlled(&hadamard(), &[0], 1, 2);
+        assert_eq!(h.data[2][2], hadamard().data[0][0]);
+        assert_eq!(h.data[3][3], hadamard().data[1][1]);
+        assert_eq!(h.data[0][0], c!(1));
+    }
+
+    #[test]
+    fn test_cnot_on() {
+        assert_eq!(cnot_on(0, 1, 2), cnot());
+        assert_eq!(
+            cnot_on(0, 2, 3) * Matrix::from_basis_string("110").unwrap(),
+            Matrix::from_basis_string("111").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_matrix_apply_gate_at() {
+        let state = Matrix::new(
+            (0..8)
+                .map(|i| vec![c!(i as f64 + 1.0, 0.5)])
+                .collect::<Vec<_>>(),
+        );
+
+        // CNOT across the spectator qbit 1, in both directions
+        assert_eq!(
+            state.apply_gate_at(&cnot(), &[0, 2], 3),
+            cnot_on(0, 2, 3) * state.clone()
+        );
+        assert_eq!(
+            state.apply_gate_at(&cnot(), &[2, 0], 3),
+            cnot_on(2, 0, 3) * state.clone()
+        );
+
+        // Adjacent targets match the tensor product
+        let h_on_1 = Matrix::identity(2)
+            .tensor(&hadamard())
+            .tensor(&Matrix::identity(2));
+        assert_eq!(
+            state.apply_gate_at(&hadamard(), &[1], 3),
+            h_on_1 * state.clone()
+        );
+        assert_eq!(
+            state.apply_gate_at(&cnot(), &[1, 2], 3),
+            Matrix::identity(2).tensor(&cnot()) * state
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_matrix_apply_gate_at_repeated_target() {
+        uniform_superposition(2).apply_gate_at(&cnot(), &[1, 1], 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_multi_controlled_overlap() {
+        multi_controlled(&hadamard(), &[1], 1, 2);
+    }
+
+    #[test]
+    fn test_uniform_superposition() {
+        let zero = Matrix::zero(4, 1).set(0, 0, c!(1));
+        let expected = hadamard().tensor(&hadamard()) * zero;
+
+        let m = uniform_superposition(2);
+        assert_eq!(m, expected);
+        assert!(f64_equal(m.norm(), 1.0));
+        assert_eq!(uniform_superposition(0), mat!(c!(1)));
+    }
+
     #[test]
     fn test_unitary_modular() {
         let a = 2;
@@ -531,8 +1994,166 @@ mod tests {
         assert_eq!(unitary_apply.data[10][0], c!(0));
         assert_eq!(unitary_apply.data[11][0], c!(0));
         assert_eq!(unitary_apply.data[62][0], c!(5));
+
+        assert!(unitary_modular(2, 3).is_unitary());
     }
 
+    #[test]
+    fn test_matrix_permutation() {
+        assert_eq!(Matrix::permutation(&[0, 2, 1, 3]).unwrap(), swap());
+        assert_eq!(Matrix::permutation(&[0, 1, 3, 2]).unwrap(), cnot());
+        assert_eq!(Matrix::permutation(&[0, 1]).unwrap(), Matrix::identity(2));
+
+        assert!(matches!(
+            Matrix::permutation(&[0, 0, 1]),
+            Err(MatrixError::InvalidPermutation(_))
+        ));
+        assert!(matches!(
+            Matrix::permutation(&[1, 2]),
+            Err(MatrixError::InvalidPermutation(_))
+        ));
+        assert!(matches!(Matrix::permutation(&[]), Err(MatrixError::Empty)));
+    }
+
+    #[test]
+    fn test_pauli_gates() {
+        for gate in [pauli_x(), pauli_y(), pauli_z()] {
+            assert!(gate.is_unitary());
+            assert!(gate.is_hermitian());
+            assert_eq!(&gate * &gate, Matrix::identity(2));
+        }
+
+        assert_eq!(pauli_y(), mat!(c!(0), c!(0, -1); c!(0, 1), c!(0)));
+        assert_eq!(&pauli_x() * &pauli_y(), pauli_z().scalar_mul(c!(0, 1)));
+    }
+
+    #[test]
+    fn test_rotation_gates() {
+        use core::f64::consts::PI;
+
+        for theta in [0.0, 0.3, PI / 2.0, PI, 5.0] {
+            assert!(rx(theta).is_unitary());
+            assert!(ry(theta).is_unitary());
+            assert!(rz(theta).is_unitary());
+        }
+
+        // Equal to the Pauli gates up to the global phase -i
+        let phase = c!(0, 1);
+        assert_eq!(rx(PI).scalar_mul(phase), pauli_x());
+        assert_eq!(ry(PI).scalar_mul(phase), pauli_y());
+        assert_eq!(rz(PI).scalar_mul(phase), pauli_z());
+
+        assert_eq!(rx(0.0), Matrix::identity(2));
+        assert_eq!(ry(0.0), Matrix::identity(2));
+        assert_eq!(rz(0.0), Matrix::identity(2));
+    }
+
+    #[test]
+    fn test_swap_and_controlled() {
+        assert!(swap().is_unitary());
+        assert_eq!(&swap() * &swap(), Matrix::identity(4));
+        assert_eq!(
+            swap().multiply(&Matrix::from_basis_string("01").unwrap()),
+            Matrix::from_basis_string("10").unwrap()
+        );
+
+        assert_eq!(controlled(&pauli_x()), cnot());
+        let cz = controlled(&pauli_z());
+        assert!(cz.is_unitary());
+        assert_eq!(cz.data[3][3], c!(-1));
+        assert_eq!(cz.trace(), c!(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "a power of two size, got 3x3")]
+    fn test_controlled_not_power_of_two() {
+        controlled(&Matrix::identity(3));
+    }
+
+    #[test]
+    fn test_toffoli() {
+        let basis = |index: usize| Matrix::zero(8, 1).set(index, 0, c!(1));
+
+        assert_eq!(toffoli().multiply(&basis(6)), basis(7));
+        assert_eq!(toffoli().multiply(&basis(7)), basis(6));
+        for index in 0..6 {
+            assert_eq!(toffoli().multiply(&basis(index)), basis(index));
+        }
+
+        assert_eq!(&toffoli() * &toffoli(), Matrix::identity(8));
+    }
+
+    #[test]
+    fn test_embed_gate() {
+        let i2 = Matrix::identity(2);
+        assert_eq!(
+            Matrix::embed_gate(&hadamard(), 1, 3),
+            i2.tensor(&hadamard()).tensor(&i2)
+        );
+        assert_eq!(Matrix::embed_gate(&hadamard(), 0, 1), hadamard());
+        assert_eq!(
+            Matrix::embed_gate(&cnot(), 1, 3),
+            Matrix::identity(2).tensor(&cnot())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Gate targets fall outside of the register")]
+    fn test_embed_gate_outside_register() {
+        Matrix::embed_gate(&cnot(), 2, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "a power of two size, got 3x3")]
+    fn test_embed_gate_not_power_of_two() {
+        Matrix::embed_gate(&Matrix::identity(3), 0, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "a power of two size, got 2x1")]
+    fn test_embed_gate_not_square() {
+        Matrix::embed_gate(&mat!(c!(1); c!(0)), 0, 3);
+    }
+
+    #[test]
+    fn test_tensor_pow() {
+        assert_eq!(hadamard().tensor_pow(0), Matrix::identity(1));
+        assert_eq!(hadamard().tensor_pow(1), hadamard());
+        assert_eq!(hadamard().tensor_pow(2), hadamard().tensor(&hadamard()));
+        assert_eq!(pauli_x().tensor_pow(3).size(), (8, 8));
+    }
+
+    #[test]
+    #[cfg(feature = "parallel")]
+    fn test_parallel_multiply() {
+        let mut a = Matrix::zero_sq(128);
+        for (i, row) in a.data.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = c!(((i * 7 + j) % 13) as f64 / 3.0, (i + 2 * j) as f64 / 11.0);
+            }
+        }
+        let b = a.adjoint();
+
+        let parallel = a.multiply(&b);
+        let (a_nonzero, b_nonzero) = (a.nonzero_columns(true), b.nonzero_columns(true));
+        let serial: Vec<Vec<C>> = (0..128)
+            .map(|i| a.product_row(&b, i, &a_nonzero[i], &b_nonzero))
+            .collect();
+        for (row, serial_row) in parallel.data.iter().zip(&serial) {
+            assert!(row.iter().zip(serial_row).all(|(v, w)| v.exact_eq(w)));
+        }
+    }
+
+    #[test]
+    fn test_hadamard_layer() {
+        assert_eq!(hadamard_layer(0), Matrix::identity(1));
+        assert_eq!(hadamard_layer(1), hadamard());
+        assert_eq!(hadamard_layer(3), hadamard().tensor_pow(3));
+        assert_eq!(
+            hadamard_layer(3).multiply(&Matrix::from_basis_string("000").unwrap()),
+            uniform_superposition(3)
+        );
+    }
 
     #[test]
     fn tetst_qft() {
@@ -547,6 +2168,29 @@ mod tests {
             c!(1),c!(0, -1),c!(-1),c!(0, 1);
         ].scalar_mul(half);
 
-        assert_eq!(m, res);
+        assert_matrix_approx_eq!(m, res, 1e-12);
+
+        for n in 1..4 {
+            assert!(quantum_fourier(n).is_unitary());
+        }
+    }
+
+    #[test]
+    fn test_matrix_approx_eq() {
+        // The roots of unity of the generated QFT carry rounding errors the exact entries lack
+        let exact = mat![
+            c!(1), c!(1), c!(1), c!(1);
+            c!(1), c!(0, 1), c!(-1), c!(0, -1);
+            c!(1), c!(-1), c!(1), c!(-1);
+            c!(1), c!(0, -1), c!(-1), c!(0, 1);
+        ]
+        .scalar_mul(c!(0.5));
+        let generated = quantum_fourier(2);
+
+        assert!(generated.approx_eq(&exact, 1e-9));
+        assert!(!generated.approx_eq(&exact, 1e-20));
+
+        assert!(!Matrix::identity(2).approx_eq(&Matrix::identity(4), 1.0));
+        assert!(!exact.approx_eq(&exact.scalar_mul(c!(0.0, 1.0)), 1e-9));
     }
 }
@@ -1,3 +1,3 @@
 pub mod complex;
 pub mod matrix;
-
+pub mod sparse;
@@ -0,0 +1,116 @@
+use alloc::{vec, vec::Vec};
+
+use crate::c;
+
+use super::{
+    complex::C,
+    matrix::{modular_mapping, Matrix},
+};
+
+// Matrix storing only its non-zero (row, col, value) entries, for operators like
+// unitary_modular that are almost entirely zero
+#[derive(Debug, Clone, PartialEq)]
+pub struct SparseMatrix {
+    size: (usize, usize),
+    entries: Vec<(usize, usize, C)>,
+}
+
+impl SparseMatrix {
+    pub fn new(size: (usize, usize), entries: Vec<(usize, usize, C)>) -> SparseMatrix {
+        assert!(
+            entries.iter().all(|(i, j, _)| *i < size.0 && *j < size.1),
+            "Sparse entries fall outside of a {}x{} matrix",
+            size.0,
+            size.1
+        );
+        SparseMatrix { size, entries }
+    }
+
+    pub fn from_dense(m: &Matrix, eps: f64) -> SparseMatrix {
+        SparseMatrix {
+            size: m.size(),
+            entries: m.iter_nonzero(eps).collect(),
+        }
+    }
+
+    pub fn to_dense(&self) -> Matrix {
+        let mut m = Matrix::zero(self.size.0, self.size.1);
+        for (i, j, v) in &self.entries {
+            m.data[*i][*j] += *v;
+        }
+        m
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        self.size
+    }
+
+    pub fn nnz(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn apply(&self, vec: &Matrix) -> Matrix {
+        assert!(
+            vec.is_vector() && vec.size().0 == self.size.1,
+            "Cannot apply a {}x{} operator to a vector of size {:?}",
+            self.size.0,
+            self.size.1,
+            vec.size()
+        );
+
+        let mut res = vec![vec![c!(0)]; self.size.0];
+        for (i, j, v) in &self.entries {
+            res[*i][0] += *v * vec.data[*j][0];
+        }
+        Matrix::new(res)
+    }
+}
+
+pub fn sparse_unitary_modular(a: usize, n: usize) -> SparseMatrix {
+    let mapping = modular_mapping(a, n);
+    let size = mapping.len();
+    let entries = mapping
+        .into_iter()
+        .enumerate()
+        .map(|(i, target)| (target, i, c!(1)))
+        .collect();
+    SparseMatrix::new((size, size), entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::matrix::matrix::{hadamard, unitary_modular};
+
+    use super::*;
+
+    #[test]
+    fn test_sparse_unitary_modular() {
+        let dense = unitary_modular(2, 3);
+        let sparse = sparse_unitary_modular(2, 3);
+
+        assert_eq!(sparse.size(), dense.size());
+        assert_eq!(sparse.nnz(), 64);
+        assert_eq!(sparse.to_dense(), dense);
+
+        for index in [0, 5, 17, 42, 63] {
+            let basis = Matrix::zero(64, 1).set(index, 0, c!(1));
+            assert_eq!(sparse.apply(&basis), dense.multiply(&basis));
+        }
+    }
+
+    #[test]
+    fn test_sparse_from_dense() {
+        let sparse = SparseMatrix::from_dense(&hadamard(), 1e-9);
+        assert_eq!(sparse.nnz(), 4);
+        assert_eq!(sparse.to_dense(), hadamard());
+
+        let v = Matrix::new(vec![vec![c!(1)], vec![c!(0)]]);
+        assert_eq!(sparse.apply(&v), hadamard().multiply(&v));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_sparse_apply_size_mismatch() {
+        sparse_unitary_modular(2, 3).apply(&Matrix::zero(8, 1));
+    }
+}
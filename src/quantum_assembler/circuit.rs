@@ -0,0 +1,151 @@
+use std::rc::Rc;
+
+use super::{
+    executor::{execute_script, execute_script_seeded, Measurements},
+    parser::{ASTNode, MemoryLocation, AST},
+    QuantumSimError,
+};
+
+// Name of the register every gate of the circuit acts on
+pub const REGISTER: &str = "R";
+
+// Typed builder producing the same AST the assembler parser would, without the string round trip
+#[derive(Debug, Clone, Default)]
+pub struct Circuit {
+    ast: AST,
+    n_qubits: usize,
+}
+
+impl Circuit {
+    pub fn new() -> Circuit {
+        Circuit::default()
+    }
+
+    pub fn initialize(mut self, n_qubits: usize) -> Circuit {
+        self.n_qubits = n_qubits;
+        self.ast.push(ASTNode::VariableAssignment(
+            REGISTER.to_string(),
+            MemoryLocation::Heap,
+            Rc::new(ASTNode::FunctionApplication(
+                "INITIALIZE".to_string(),
+                vec![ASTNode::Literal(n_qubits.to_string())],
+            )),
+        ));
+        self
+    }
+
+    pub fn h(self, qubit: usize) -> Circuit {
+        assert!(qubit < self.n_qubits, "Qubit {} outside of register", qubit);
+
+        // I_before tensor H tensor I_after
+        let before = 2_usize.pow(qubit as u32);
+        let after = 2_usize.pow((self.n_qubits - qubit - 1) as u32);
+        let gate = ASTNode::FunctionApplication(
+            "TENSOR".to_string(),
+            vec![
+                ASTNode::FunctionApplication(
+                    "TENSOR".to_string(),
+                    vec![
+                        ASTNode::Literal(format!("G_I_{}", before)),
+                        ASTNode::Literal("G_H".to_string()),
+                    ],
+                ),
+                ASTNode::Literal(format!("G_I_{}", after)),
+            ],
+        );
+
+        self.apply(gate)
+    }
+
+    pub fn cnot(self, control: usize, target: usize) -> Circuit {
+        assert!(
+            control != target && control < self.n_qubits && target < self.n_qubits,
+            "Invalid CNOT from qubit {} to {}",
+            control,
+            target
+        );
+
+        let gate = ASTNode::Literal(format!("G_CX_{}_{}_{}", control, target, self.n_qubits));
+        self.apply(gate)
+    }
+
+    pub fn measure(mut self, register: &str, label: &str) -> Circuit {
+        self.ast.push(ASTNode::VariableAssignment(
+            label.to_string(),
+            MemoryLocation::Measurement,
+            Rc::new(ASTNode::FunctionApplication(
+                "MEASURE".to_string(),
+                vec![ASTNode::Identifier(register.to_string())],
+            )),
+        ));
+        self
+    }
+
+    pub fn ast(&self) -> &AST {
+        &self.ast
+    }
+
+    pub fn run(&self) -> Result<Measurements, QuantumSimError> {
+        Ok(execute_script(self.ast.clone())?)
+    }
+
+    pub fn run_seeded(&self, seed: u64) -> Result<Measurements, QuantumSimError> {
+        Ok(execute_script_seeded(self.ast.clone(), seed)?)
+    }
+
+    fn apply(mut self, gate: ASTNode) -> Circuit {
+        self.ast.push(ASTNode::VariableAssignment(
+            REGISTER.to_string(),
+            MemoryLocation::Heap,
+            Rc::new(ASTNode::FunctionApplication(
+                "APPLY".to_string(),
+                vec![gate, ASTNode::Identifier(REGISTER.to_string())],
+            )),
+        ));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::quantum_assembler::parser::parse;
+
+    use super::*;
+
+    #[test]
+    fn test_circuit_matches_script() {
+        let circuit = Circuit::new().initialize(2).measure(REGISTER, "RES");
+        let script = parse("INITIALIZE R 2\nMEASURE R RES".to_string()).unwrap();
+
+        assert_eq!(circuit.ast(), &script);
+    }
+
+    #[test]
+    fn test_bell_state() {
+        let circuit = Circuit::new()
+            .initialize(2)
+            .h(0)
+            .cnot(0, 1)
+            .measure(REGISTER, "RES");
+
+        let mut outcomes = vec![];
+        for seed in 0..20 {
+            let res = circuit.run_seeded(seed).unwrap();
+            let outcome = res.get("RES").unwrap().1.clone();
+
+            // Both qubits always collapse to the same value
+            assert!(outcome == "00" || outcome == "11");
+            outcomes.push(outcome);
+        }
+
+        assert!(outcomes.contains(&"00".to_string()));
+        assert!(outcomes.contains(&"11".to_string()));
+        assert!(circuit.run().is_ok());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_gate_outside_register() {
+        let _ = Circuit::new().initialize(2).h(2);
+    }
+}
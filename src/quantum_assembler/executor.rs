@@ -1,19 +1,54 @@
-use std::{collections::HashMap, error, f64::consts::PI, fmt};
+use std::{
+    collections::HashMap,
+    error,
+    f64::consts::PI,
+    fmt,
+    time::{Duration, Instant},
+};
+
+use rand::{rngs::StdRng, SeedableRng};
 
 use crate::{
-    c,
-    matrix::{complex::C, matrix::{cnot, hadamard, phase_shift, quantum_fourier, unitary_modular, Matrix}},
+    c, mat,
+    matrix::{
+        complex::C,
+        matrix::{
+            cnot, controlled, hadamard, hadamard_layer, multi_controlled, pauli_x, pauli_y,
+            pauli_z, phase_shift, quantum_fourier, uniform_superposition, unitary_modular, Matrix,
+            MatrixError,
+        },
+    },
 };
 
 use super::{
-    parser::{ASTNode, MemoryLocation, AST},
-    quantum_sim::{measure_partial_vec, measure_vec, qbit_length},
+    parser::{ASTNode, MemoryLocation, NumberedAST},
+    quantum_sim::{
+        fidelity, measure_partial_vec_seeded, measure_vec_seeded, operator_qbit_length,
+        qbit_length, trace_distance,
+    },
 };
 
 #[derive(Debug)]
 pub enum RunTimeError {
     SyntaxError(String), // TOO GENERIC
     NotImplemented,
+    Line(usize, Box<RunTimeError>),
+}
+
+impl RunTimeError {
+    pub fn at_line(self, line: usize) -> RunTimeError {
+        match self {
+            RunTimeError::Line(_, _) => self,
+            _ => RunTimeError::Line(line, Box::new(self)),
+        }
+    }
+
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            RunTimeError::Line(line, _) => Some(*line),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for RunTimeError {
@@ -21,6 +56,7 @@ impl fmt::Display for RunTimeError {
         match self {
             RunTimeError::SyntaxError(mess) => write!(f, "Syntax error: {}", mess),
             RunTimeError::NotImplemented => write!(f, "Not implemented"),
+            RunTimeError::Line(line, err) => write!(f, "line {}: {}", line, err),
         }
     }
 }
@@ -30,17 +66,35 @@ impl error::Error for RunTimeError {
         match self {
             RunTimeError::SyntaxError(_) => "Syntax error in code",
             RunTimeError::NotImplemented => "Not implemented",
+            RunTimeError::Line(_, _) => "Error in code at a given line",
         }
     }
 }
 
 type Heap = HashMap<String, LiteralValue>;
-type Measurements = HashMap<String, (Matrix, String)>;
+pub type Measurements = HashMap<String, (Matrix, String)>;
+pub type Timings = Vec<(String, Duration)>;
+// (variable name, rendered value) in the order the PRINT statements ran
+pub type PrintLog = Vec<(String, String)>;
+pub type MatrixHeap = HashMap<String, Matrix>;
 
 #[derive(Debug)]
 struct QuantumMemory {
     heap: Heap,
     measurements: Measurements,
+    log: PrintLog,
+    rng: StdRng,
+}
+
+impl QuantumMemory {
+    fn new(rng: StdRng) -> QuantumMemory {
+        QuantumMemory {
+            heap: HashMap::new(),
+            measurements: HashMap::new(),
+            log: vec![],
+            rng,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -90,18 +144,14 @@ fn validate_param_len(
 }
 
 fn parse_params_from_prefebs(lit: &String, expected: usize) -> Result<Vec<usize>, RunTimeError> {
-    let re = regex::Regex::new(r"\d+");
-
-    if re.is_err() {
-        return Err(RunTimeError::SyntaxError("Invalid literal".to_string()));
-    }
+    let re = regex::Regex::new(r"\d+")
+        .map_err(|_| RunTimeError::SyntaxError("Invalid literal".to_string()))?;
 
-    let re = re.unwrap();
-
-    let nmbrs: Vec<usize> = re
+    let nmbrs = re
         .find_iter(lit)
-        .map(|m| m.as_str().parse::<usize>().unwrap())
-        .collect();
+        .map(|m| m.as_str().parse::<usize>())
+        .collect::<Result<Vec<usize>, _>>()
+        .map_err(|_| RunTimeError::SyntaxError(format!("Invalid literal {}", lit)))?;
 
     if nmbrs.len() != expected {
         return Err(RunTimeError::SyntaxError("Invalid literal".to_string()));
@@ -114,31 +164,74 @@ fn parse_literal(v: &String) -> Result<LiteralValue, RunTimeError> {
     match v.as_str() {
         "G_H" => Ok(LiteralValue::Matrix(hadamard())),
         "G_CNOT" => Ok(LiteralValue::Matrix(cnot())),
+        "G_X" => Ok(LiteralValue::Matrix(pauli_x())),
+        "G_Y" => Ok(LiteralValue::Matrix(pauli_y())),
+        "G_Z" => Ok(LiteralValue::Matrix(pauli_z())),
         _ => {
             if v.starts_with("G_R_") {
-                let nmbrs = parse_params_from_prefebs(v, 1).unwrap();
+                let nmbrs = parse_params_from_prefebs(v, 1)?;
                 return Ok(LiteralValue::Matrix(phase_shift(PI / (nmbrs[0] as f64))));
             }
             if v.starts_with("G_I_") {
-                let nmbrs = parse_params_from_prefebs(v, 1).unwrap();
+                let nmbrs = parse_params_from_prefebs(v, 1)?;
                 return Ok(LiteralValue::Matrix(Matrix::identity(nmbrs[0])));
             }
             if v.starts_with("G_Uf_") {
-                let nmbrs = parse_params_from_prefebs(v, 2).unwrap();
+                let nmbrs = parse_params_from_prefebs(v, 2)?;
                 return Ok(LiteralValue::Matrix(unitary_modular(nmbrs[0], nmbrs[1])));
             }
             if v.starts_with("G_QFTI_") {
-                let nmbrs = parse_params_from_prefebs(v, 1).unwrap();
+                let nmbrs = parse_params_from_prefebs(v, 1)?;
                 return Ok(LiteralValue::Matrix(quantum_fourier(nmbrs[0]).adjoint()));
             }
-            if v.parse::<i32>().is_ok() {
-                return Ok(LiteralValue::Int(v.parse::<i32>().unwrap()));
+            if v.starts_with("G_CX_") {
+                // G_CX_<control>_<target>_<nr qbits>
+                let nmbrs = parse_params_from_prefebs(v, 3)?;
+                let (control, target, n_qubits) = (nmbrs[0], nmbrs[1], nmbrs[2]);
+                if control == target || control >= n_qubits || target >= n_qubits {
+                    return Err(RunTimeError::SyntaxError(format!("Invalid literal {}", v)));
+                }
+                return Ok(LiteralValue::Matrix(multi_controlled(
+                    &pauli_x(),
+                    &[control],
+                    target,
+                    n_qubits,
+                )));
+            }
+            if let Some(entries) = v.strip_prefix("G_MAT_") {
+                return parse_inline_matrix(entries).map(LiteralValue::Matrix);
+            }
+            if let Ok(n) = v.parse::<i32>() {
+                return Ok(LiteralValue::Int(n));
             }
             Err(RunTimeError::SyntaxError("Invalid literal".to_string()))
         }
     }
 }
 
+// Row-major amplitudes separated by underscores, e.g. 0_1_1_0 or 0.5_0.5i_-0.5i_0.5
+fn parse_inline_matrix(entries: &str) -> Result<Matrix, RunTimeError> {
+    let invalid = || RunTimeError::SyntaxError(format!("Invalid matrix literal G_MAT_{}", entries));
+
+    let amplitudes = entries
+        .split('_')
+        .map(|entry| entry.parse::<C>())
+        .collect::<Result<Vec<C>, _>>()
+        .map_err(|_| invalid())?;
+
+    let size = (amplitudes.len() as f64).sqrt().round() as usize;
+    if size == 0 || size * size != amplitudes.len() {
+        return Err(invalid());
+    }
+
+    Ok(Matrix::new(
+        amplitudes
+            .chunks(size)
+            .map(|row| row.to_vec())
+            .collect::<Vec<_>>(),
+    ))
+}
+
 fn parse_identifier(
     var_name: &String,
     memory: &QuantumMemory,
@@ -155,7 +248,7 @@ fn parse_var_assignment(
     memory_loc: &MemoryLocation,
     memory: &mut QuantumMemory,
 ) -> Result<Option<LiteralValue>, RunTimeError> {
-    let val = execute_ast_node(val, memory).unwrap();
+    let val = execute_ast_node(val, memory)?;
     match val {
         Some(val) => {
             match (memory_loc, val.clone()) {
@@ -169,6 +262,12 @@ fn parse_var_assignment(
                     memory.heap.insert(var_name.clone(), val.1);
                 }
                 (MemoryLocation::Measurement, (_, LiteralValue::Measurement(a, b))) => {
+                    if memory.measurements.contains_key(var_name) {
+                        return Err(RunTimeError::SyntaxError(format!(
+                            "Measurement label {} is already in use",
+                            var_name
+                        )));
+                    }
                     memory.measurements.insert(var_name.clone(), (a, b));
                 }
                 _ => return Err(RunTimeError::SyntaxError("Invalid assignment".to_string())),
@@ -179,58 +278,169 @@ fn parse_var_assignment(
     }
 }
 
+fn matrix_error(func: &str, e: MatrixError) -> RunTimeError {
+    RunTimeError::SyntaxError(format!("{} failed: {}", func, e))
+}
+
+// A register over n qbits holds 2^n amplitudes, errors if n is negative or 2^n overflows
+fn register_size(func: &str, n_qbits: i32) -> Result<usize, RunTimeError> {
+    u32::try_from(n_qbits)
+        .ok()
+        .and_then(|n| 2usize.checked_pow(n))
+        .ok_or_else(|| {
+            RunTimeError::SyntaxError(format!("Invalid register size {} for {}", n_qbits, func))
+        })
+}
+
+// INITIALIZE R [a b ..] builds the normalized state with the listed amplitudes
+fn parse_vector(params: &[ASTNode]) -> Result<Matrix, RunTimeError> {
+    let amplitudes = params
+        .iter()
+        .map(|p| match p {
+            ASTNode::Literal(v) | ASTNode::Identifier(v) => v
+                .parse::<C>()
+                .map_err(|e| RunTimeError::SyntaxError(format!("Invalid amplitude: {}", e))),
+            _ => Err(RunTimeError::SyntaxError(format!(
+                "Invalid amplitude {}",
+                p
+            ))),
+        })
+        .collect::<Result<Vec<C>, RunTimeError>>()?;
+
+    if !amplitudes.len().is_power_of_two() {
+        return Err(RunTimeError::SyntaxError(format!(
+            "Invalid vector for INITIALIZE, got {} amplitudes instead of a power of two",
+            amplitudes.len()
+        )));
+    }
+
+    let column: Vec<Vec<C>> = amplitudes.into_iter().map(|a| vec![a]).collect();
+    Matrix::new(column)
+        .normalized()
+        .map_err(|e| matrix_error("INITIALIZE", e))
+}
+
 fn parse_func_application(
     func: &String,
     params: &Vec<ASTNode>,
     memory: &mut QuantumMemory,
 ) -> Result<Option<(String, LiteralValue)>, RunTimeError> {
+    if func == "VECTOR" {
+        let vector = parse_vector(params)?;
+        return Ok(Some((func.clone(), LiteralValue::Matrix(vector))));
+    }
+
     let params = params
         .iter()
-        .map(|p| execute_ast_node(p, memory).unwrap())
-        .filter_map(|p| p)
+        .map(|p| execute_ast_node(p, memory))
+        .collect::<Result<Vec<_>, RunTimeError>>()?
+        .into_iter()
+        .flatten()
         .collect::<Vec<(String, LiteralValue)>>();
 
     match &func[..] {
         "INITIALIZE" => {
-            validate_param_len(&params, 1).unwrap();
+            if let [(_, LiteralValue::Matrix(vector))] = params.as_slice() {
+                if !vector.is_vector() {
+                    return Err(RunTimeError::SyntaxError(
+                        "Input invalid for INITIALIZE, should be a vector".to_string(),
+                    ));
+                }
+                return Ok(Some((func.clone(), LiteralValue::Matrix(vector.clone()))));
+            }
+
+            // INITIALIZE R n [idx], the optional idx selects the basis state
+            if params.len() != 2 {
+                validate_param_len(&params, 1)?;
+            }
+
+            let value = unwrap_int(&params[0].1)?;
+            let index = match params.get(1) {
+                Some((_, idx)) => *unwrap_int(idx)?,
+                None => 0,
+            };
+
+            let size = register_size(func, *value)?;
+            if index < 0 || index as usize >= size {
+                return Err(RunTimeError::SyntaxError(format!(
+                    "Invalid basis state {} for INITIALIZE, should be below {}",
+                    index, size
+                )));
+            }
+
+            let matrix = Matrix::zero(size, 1);
+            Ok(Some((
+                func.clone(),
+                LiteralValue::Matrix(matrix.set(index as usize, 0, c!(1))),
+            )))
+        }
+        "SUPERPOSE" => {
+            validate_param_len(&params, 1)?;
 
-            let value = unwrap_int(&params[0].1).unwrap();
+            let value = unwrap_int(&params[0].1)?;
+            register_size(func, *value)?;
 
-            let matrix = Matrix::zero((2 as u32).clone().pow(value.clone() as u32) as usize, 1);
             Ok(Some((
                 func.clone(),
-                LiteralValue::Matrix(matrix.set(0, 0, c!(1))),
+                LiteralValue::Matrix(uniform_superposition(*value as usize)),
             )))
         }
         "INVERSE" => {
-            validate_param_len(&params, 1).unwrap();
+            validate_param_len(&params, 1)?;
+
+            let matrix = unwrap_matrix(&params[0].1)?;
+
+            let inverse = matrix.inverse().ok_or_else(|| {
+                RunTimeError::SyntaxError(
+                    "Input invalid for INVERSE, should be an invertible square matrix".to_string(),
+                )
+            })?;
+
+            Ok(Some((func.clone(), LiteralValue::Matrix(inverse))))
+        }
+        "CONTROLLED" => {
+            validate_param_len(&params, 1)?;
 
-            let matrix = unwrap_matrix(&params[0].1).unwrap();
+            let matrix = unwrap_matrix(&params[0].1)?;
 
-            if !matrix.is_hermitian() {
+            if operator_qbit_length(matrix).is_none() {
                 return Err(RunTimeError::SyntaxError(
-                    "Input invalid for INVERSE, should be a hermetian matrix".to_string(),
+                    "Input invalid for CONTROLLED, should be a square gate over n qbits"
+                        .to_string(),
                 ));
             }
 
-            Ok(Some((func.clone(), LiteralValue::Matrix(matrix.adjoint()))))
+            Ok(Some((
+                func.clone(),
+                LiteralValue::Matrix(controlled(matrix)),
+            )))
         }
         "TENSOR" => {
-            validate_param_len(&params, 2).unwrap();
+            validate_param_len(&params, 2)?;
 
-            let matrix1 = unwrap_matrix(&params[0].1).unwrap();
-            let matrix2 = unwrap_matrix(&params[1].1).unwrap();
+            let matrix1 = unwrap_matrix(&params[0].1)?;
+            let matrix2 = unwrap_matrix(&params[1].1)?;
 
             Ok(Some((
                 func.clone(),
-                LiteralValue::Matrix(matrix1.tensor(matrix2)),
+                LiteralValue::Matrix(
+                    matrix1
+                        .try_tensor(matrix2)
+                        .map_err(|e| matrix_error(func, e))?,
+                ),
             )))
         }
         "CONCAT" => {
-            validate_param_len(&params, 2).unwrap();
+            validate_param_len(&params, 2)?;
 
-            let matrix1 = unwrap_matrix(&params[0].1).unwrap();
-            let matrix2 = unwrap_matrix(&params[1].1).unwrap();
+            let matrix1 = unwrap_matrix(&params[0].1)?;
+            let matrix2 = unwrap_matrix(&params[1].1)?;
+
+            if [matrix1, matrix2].iter().any(|m| m.size().0 != m.size().1) {
+                return Err(RunTimeError::SyntaxError(
+                    "CONCAT composes gates and requires square matrices, use APPLY to apply a gate to a vector".to_string(),
+                ));
+            }
 
             if matrix1.size() != matrix2.size() {
                 return Err(RunTimeError::SyntaxError(
@@ -240,34 +450,91 @@ fn parse_func_application(
 
             Ok(Some((
                 func.clone(),
-                LiteralValue::Matrix(matrix1 * matrix2),
+                LiteralValue::Matrix(
+                    matrix1
+                        .try_multiply(matrix2)
+                        .map_err(|e| matrix_error(func, e))?,
+                ),
             )))
         }
         "APPLY" => {
-            validate_param_len(&params, 2).unwrap();
+            validate_param_len(&params, 2)?;
+
+            let matrix = unwrap_matrix(&params[0].1)?;
+            let vector = unwrap_matrix(&params[1].1)?;
 
-            let matrix = unwrap_matrix(&params[0].1).unwrap();
-            let vector = unwrap_matrix(&params[1].1).unwrap();
+            let matrix_qbits = operator_qbit_length(matrix);
+            if !vector.is_vector() || matrix_qbits != Some(qbit_length(vector)) {
+                return Err(RunTimeError::SyntaxError(
+                    "Input invalid for APPLY, first arg should be a unitary matrix & the second arg should be a vector over the same number of qbits".to_string(),
+                ));
+            }
 
-            if !vector.is_vector() || vector.size().0 != matrix.size().1 {
-                println!("Vector{:?} x Matrix{:?}, herm({})", vector.size(), matrix.size(), matrix.is_hermitian());
+            // Gates have to be unitary, Hermitian is neither required (QFT) nor sufficient
+            if !matrix.is_unitary() {
                 return Err(RunTimeError::SyntaxError(
-                    "Input invalid for APPLY, first arg should be a hermetian matrix & the second arg should be vector with equal columns".to_string(),
+                    "Input invalid for APPLY, first arg should be a unitary matrix".to_string(),
                 ));
             }
 
-            Ok(Some((func.clone(), LiteralValue::Matrix(matrix * vector))))
+            let res = matrix
+                .try_multiply(vector)
+                .map_err(|e| matrix_error(func, e))?;
+            Ok(Some((func.clone(), LiteralValue::Matrix(res))))
+        }
+        "PRINT" => {
+            validate_param_len(&params, 1)?;
+
+            let (var_name, value) = &params[0];
+            let printed = match value {
+                LiteralValue::Matrix(m) | LiteralValue::Measurement(m, _) => m.to_string_grid(),
+                LiteralValue::Int(n) => n.to_string(),
+                LiteralValue::Selection(..) => {
+                    return Err(RunTimeError::SyntaxError(
+                        "Input invalid for PRINT, should be a matrix or an integer".to_string(),
+                    ))
+                }
+            };
+            memory.log.push((var_name.clone(), printed));
+
+            Ok(None)
+        }
+        "QFT" | "IQFT" | "HALL" => {
+            validate_param_len(&params, 1)?;
+
+            let vector = unwrap_matrix(&params[0].1)?;
+            if !vector.is_vector() {
+                return Err(RunTimeError::SyntaxError(format!(
+                    "Input invalid for {}, should be a register",
+                    func
+                )));
+            }
+
+            let n_qubits = qbit_length(vector);
+            let operator = match func.as_str() {
+                "QFT" => quantum_fourier(n_qubits),
+                "IQFT" => quantum_fourier(n_qubits).adjoint(),
+                _ => hadamard_layer(n_qubits),
+            };
+            let res = operator
+                .try_multiply(vector)
+                .map_err(|e| matrix_error(func, e))?;
+            Ok(Some((func.clone(), LiteralValue::Matrix(res))))
         }
         "SELECT" => {
-            validate_param_len(&params, 3).unwrap();
+            validate_param_len(&params, 3)?;
 
             let key = params[0].0.clone();
-            let vector = unwrap_matrix(&params[0].1).unwrap();
-            let start = unwrap_int(&params[1].1).unwrap();
-            let end = unwrap_int(&params[2].1).unwrap();
-
-            let qbit_len = qbit_length(vector);
-            if !vector.is_vector() || start > end || (*end as usize) > qbit_len {
+            let vector = unwrap_matrix(&params[0].1)?;
+            let start = unwrap_int(&params[1].1)?;
+            let end = unwrap_int(&params[2].1)?;
+
+            // is_vector first, qbit_length panics on anything else
+            if !vector.is_vector()
+                || *start < 0
+                || start > end
+                || *end as usize > qbit_length(vector)
+            {
                 return Err(RunTimeError::SyntaxError(
                     "Invalid range for SELECT".to_string(),
                 ));
@@ -284,12 +551,9 @@ fn parse_func_application(
             )))
         }
         "MEASURE" => {
-            validate_param_len(&params, 1).unwrap();
-
-            let vec = unwrap_matrix(&params[0].1);
+            validate_param_len(&params, 1)?;
 
-            if vec.is_ok() {
-                let vec = vec.unwrap();
+            if let Ok(vec) = unwrap_matrix(&params[0].1) {
                 if !vec.is_vector() {
                     return Err(RunTimeError::SyntaxError(
                         "Invalid input for MEASURE, should be a vector".to_string(),
@@ -298,13 +562,16 @@ fn parse_func_application(
 
                 return Ok(Some((
                     func.clone(),
-                    LiteralValue::Measurement(vec.clone(), measure_vec(vec)),
+                    LiteralValue::Measurement(
+                        vec.clone(),
+                        measure_vec_seeded(vec, &mut memory.rng),
+                    ),
                 )));
             }
 
-            let (key, _, from, to) = unwrap_selection(&params[0].1).unwrap();
-            let matrix = memory.heap.get(key).unwrap().clone();
-            let vec = unwrap_matrix(&matrix).unwrap();
+            let (key, _, from, to) = unwrap_selection(&params[0].1)?;
+            let matrix = parse_identifier(key, memory)?;
+            let vec = unwrap_matrix(&matrix)?;
 
             if !vec.is_vector() {
                 return Err(RunTimeError::SyntaxError(
@@ -312,7 +579,7 @@ fn parse_func_application(
                 ));
             }
 
-            let res = measure_partial_vec(vec, *from, *to);
+            let res = measure_partial_vec_seeded(vec, *from, *to, &mut memory.rng);
 
             memory
                 .heap
@@ -320,7 +587,57 @@ fn parse_func_application(
 
             Ok(Some((
                 func.clone(),
-                LiteralValue::Measurement(res.clone(), measure_vec(&res)),
+                LiteralValue::Measurement(res.clone(), measure_vec_seeded(&res, &mut memory.rng)),
+            )))
+        }
+        "ADD" | "SUB" | "MUL" => {
+            validate_param_len(&params, 2)?;
+
+            let (a, b) = match (&params[0].1, &params[1].1) {
+                (LiteralValue::Int(a), LiteralValue::Int(b)) => (*a, *b),
+                _ => {
+                    return Err(RunTimeError::SyntaxError(format!(
+                        "Invalid input for {}, both operands should be integers",
+                        func
+                    )))
+                }
+            };
+
+            let res = match &func[..] {
+                "ADD" => a.checked_add(b),
+                "SUB" => a.checked_sub(b),
+                _ => a.checked_mul(b),
+            };
+
+            match res {
+                Some(res) => Ok(Some((func.clone(), LiteralValue::Int(res)))),
+                None => Err(RunTimeError::SyntaxError(format!(
+                    "Integer overflow in {} {} {}",
+                    func, a, b
+                ))),
+            }
+        }
+        "FIDELITY" | "TRACE_DIST" => {
+            validate_param_len(&params, 2)?;
+
+            let a = unwrap_matrix(&params[0].1)?;
+            let b = unwrap_matrix(&params[1].1)?;
+
+            if !a.is_vector() || !b.is_vector() || a.size() != b.size() {
+                return Err(RunTimeError::SyntaxError(format!(
+                    "Invalid input for {}, should be two registers of equal size",
+                    func
+                )));
+            }
+
+            let value = match &func[..] {
+                "FIDELITY" => fidelity(a, b),
+                _ => trace_distance(a, b),
+            };
+
+            Ok(Some((
+                func.clone(),
+                LiteralValue::Measurement(mat![c!(value)], format!("{:.6}", value)),
             )))
         }
         _ => Err(RunTimeError::NotImplemented),
@@ -332,41 +649,151 @@ fn execute_ast_node(
     memory: &mut QuantumMemory,
 ) -> Result<Option<(String, LiteralValue)>, RunTimeError> {
     match ast_node {
-        ASTNode::Literal(val) => Ok(Some(("_".to_string(), parse_literal(val).unwrap()))),
+        ASTNode::Literal(val) => Ok(Some(("_".to_string(), parse_literal(val)?))),
         ASTNode::Identifier(var_name) => Ok(Some((
             var_name.clone(),
-            parse_identifier(var_name, memory).unwrap(),
+            parse_identifier(var_name, memory)?,
         ))),
         ASTNode::VariableAssignment(var_name, memory_loc, val) => {
-            parse_var_assignment(var_name, &*val, memory_loc, memory).unwrap();
+            parse_var_assignment(var_name, val, memory_loc, memory)?;
             Ok(None)
         }
         ASTNode::FunctionApplication(func, params) => parse_func_application(func, params, memory),
+        ASTNode::Repeat(_, _) => Err(RunTimeError::SyntaxError(
+            "REPEAT blocks should be expanded before execution".to_string(),
+        )),
     }
 }
 
-pub fn execute_script(ast: AST) -> Result<HashMap<String, (Matrix, String)>, RunTimeError> {
-    let heap = HashMap::<String, LiteralValue>::new();
-    let measurements = HashMap::<String, (Matrix, String)>::new();
+// Unrolls (nested) REPEAT blocks, the body nodes keep their own source lines
+fn expand_repeats(ast: NumberedAST) -> NumberedAST {
+    NumberedAST(
+        ast.0
+            .into_iter()
+            .flat_map(|(line, node)| match node {
+                ASTNode::Repeat(count, body) => {
+                    let body = expand_repeats(body).0;
+                    (0..count).flat_map(|_| body.clone()).collect()
+                }
+                _ => vec![(line, node)],
+            })
+            .collect(),
+    )
+}
+
+// Executes a single node, tagging any error with the source line when it is known
+fn execute_numbered_node(
+    line: Option<usize>,
+    node: &ASTNode,
+    memory: &mut QuantumMemory,
+) -> Result<Option<(String, LiteralValue)>, RunTimeError> {
+    execute_ast_node(node, memory).map_err(|e| match line {
+        Some(line) => e.at_line(line),
+        None => e,
+    })
+}
+
+pub fn execute_script(
+    ast: impl Into<NumberedAST>,
+) -> Result<HashMap<String, (Matrix, String)>, RunTimeError> {
+    let memory = execute_with_memory(ast.into(), QuantumMemory::new(StdRng::from_entropy()))?;
+    Ok(memory.measurements)
+}
+
+pub fn execute_script_seeded(
+    ast: impl Into<NumberedAST>,
+    seed: u64,
+) -> Result<Measurements, RunTimeError> {
+    let memory = execute_with_memory(ast.into(), QuantumMemory::new(StdRng::seed_from_u64(seed)))?;
+    Ok(memory.measurements)
+}
 
-    let mut memory = QuantumMemory { heap, measurements };
+pub fn execute_script_logged(
+    ast: impl Into<NumberedAST>,
+) -> Result<(Measurements, PrintLog), RunTimeError> {
+    let memory = execute_with_memory(ast.into(), QuantumMemory::new(StdRng::from_entropy()))?;
+    Ok((memory.measurements, memory.log))
+}
 
+// Also exposes the final matrix valued variables, e.g. an operator or an unmeasured register
+pub fn execute_script_with_heap(
+    ast: impl Into<NumberedAST>,
+) -> Result<(Measurements, MatrixHeap), RunTimeError> {
+    let memory = execute_with_memory(ast.into(), QuantumMemory::new(StdRng::from_entropy()))?;
+    let heap = memory
+        .heap
+        .into_iter()
+        .filter_map(|(name, value)| match value {
+            LiteralValue::Matrix(m) => Some((name, m)),
+            _ => None,
+        })
+        .collect();
+    Ok((memory.measurements, heap))
+}
+
+fn execute_with_memory(
+    ast: NumberedAST,
+    memory: QuantumMemory,
+) -> Result<QuantumMemory, RunTimeError> {
+    execute_with_hook(ast, memory, |_, _| {})
+}
+
+// The single execution loop, on_node is called after every executed node with its runtime
+fn execute_with_hook(
+    ast: NumberedAST,
+    mut memory: QuantumMemory,
+    mut on_node: impl FnMut(&ASTNode, Duration),
+) -> Result<QuantumMemory, RunTimeError> {
     // LOOP TROUGH AST AND RUN
-    for node in ast {
-        // println!("{:?}", node);
+    for (line, node) in expand_repeats(ast).0 {
+        // println!("{}", node);
         // println!("{:?}", memory.heap);
-        execute_ast_node(&node, &mut memory).unwrap();
+        let start = Instant::now();
+        execute_numbered_node(line, &node, &mut memory)?;
+        on_node(&node, start.elapsed());
     }
 
-    Ok(memory.measurements)
+    Ok(memory)
+}
+
+fn describe_node(node: &ASTNode) -> String {
+    match node {
+        ASTNode::VariableAssignment(var_name, _, val) => match &**val {
+            ASTNode::FunctionApplication(func, _) => format!("{} {}", func, var_name),
+            _ => var_name.clone(),
+        },
+        ASTNode::FunctionApplication(func, _) => func.clone(),
+        ASTNode::Repeat(count, _) => format!("REPEAT {}", count),
+        ASTNode::Literal(val) | ASTNode::Identifier(val) => val.clone(),
+    }
+}
+
+pub fn execute_script_timed(
+    ast: impl Into<NumberedAST>,
+) -> Result<(Measurements, Timings), RunTimeError> {
+    let mut timings = vec![];
+    let memory = execute_with_hook(
+        ast.into(),
+        QuantumMemory::new(StdRng::from_entropy()),
+        |node, elapsed| timings.push((describe_node(node), elapsed)),
+    )?;
+
+    Ok((memory.measurements, timings))
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{mat, quantum_assembler::parser::parse};
+    use crate::{
+        assert_matrix_approx_eq,
+        quantum_assembler::parser::{parse, parse_numbered},
+        util::f64_equal,
+    };
 
     use super::*;
 
+    // Tolerance for amplitudes derived from sqrt / trig, integer-exact results use assert_eq!
+    const EPS: f64 = 1e-9;
+
     #[test]
     fn test_init_and_measure_executor() {
         let ast = parse(
@@ -407,10 +834,11 @@ mod tests {
 
         let res = res.unwrap();
         assert!(res.contains_key("RES"));
-        assert_eq!(
-            res.get("RES").unwrap().0,
-            mat![c!(0.5); c!(0.5);c!(0.5);c!(0.5)]
-        );
+        assert!(res
+            .get("RES")
+            .unwrap()
+            .0
+            .approx_eq(&mat![c!(0.5); c!(0.5); c!(0.5); c!(0.5)], EPS));
     }
 
     #[test]
@@ -438,4 +866,595 @@ mod tests {
         let res2 = res.get("RES2").unwrap();
         assert!(res2.1 == "11" || res2.1 == "00");
     }
+
+    #[test]
+    fn test_nested_apply() {
+        let ast = parse(
+            "
+        INITIALIZE R 2
+        APPLY (TENSOR G_H G_H) R
+        MEASURE R RES
+        "
+            .to_string(),
+        );
+        assert!(ast.is_ok());
+
+        let res = execute_script(ast.unwrap());
+
+        assert!(res.is_ok());
+
+        let res = res.unwrap();
+        assert!(res
+            .get("RES")
+            .unwrap()
+            .0
+            .approx_eq(&mat![c!(0.5); c!(0.5); c!(0.5); c!(0.5)], EPS));
+    }
+
+    #[test]
+    fn test_superpose() {
+        let ast = parse(
+            "
+        SUPERPOSE R 2
+        MEASURE R RES
+        "
+            .to_string(),
+        );
+        assert!(ast.is_ok());
+
+        let res = execute_script(ast.unwrap());
+
+        assert!(res.is_ok());
+
+        let res = res.unwrap();
+        assert!(res
+            .get("RES")
+            .unwrap()
+            .0
+            .approx_eq(&mat![c!(0.5); c!(0.5); c!(0.5); c!(0.5)], EPS));
+    }
+
+    #[test]
+    fn test_superpose_negative_size() {
+        let ast = parse("SUPERPOSE R -1".to_string()).unwrap();
+        let err = execute_script(ast).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Invalid register size -1 for SUPERPOSE"));
+    }
+
+    #[test]
+    fn test_superpose_oversized() {
+        let ast = parse("SUPERPOSE R 70".to_string()).unwrap();
+        let err = execute_script(ast).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Invalid register size 70 for SUPERPOSE"));
+    }
+
+    #[test]
+    fn test_execute_script_timed() {
+        let ast = parse(
+            "
+        INITIALIZE R 2
+        U TENSOR G_H G_H
+        APPLY U R
+        MEASURE R RES
+        "
+            .to_string(),
+        );
+        assert!(ast.is_ok());
+
+        let res = execute_script_timed(ast.unwrap());
+
+        assert!(res.is_ok());
+
+        let (measurements, timings) = res.unwrap();
+        assert!(measurements.contains_key("RES"));
+        assert_eq!(timings.len(), 4);
+        assert_eq!(timings[0].0, "INITIALIZE R");
+        assert_eq!(timings[1].0, "TENSOR U");
+    }
+
+    #[test]
+    fn test_apply_qbit_mismatch() {
+        let ast = parse(
+            "
+        INITIALIZE R 1
+        U TENSOR G_H G_H
+        APPLY U R
+        "
+            .to_string(),
+        );
+        assert!(ast.is_ok());
+        assert!(execute_script(ast.unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_tensor_n_matches_manual() {
+        let manual = parse(
+            "
+        U TENSOR G_H G_H
+        U TENSOR U G_H
+        U TENSOR U G_H
+        U TENSOR U G_H
+        "
+            .to_string(),
+        );
+        let expanded = parse(
+            "
+        U TENSOR G_H G_H
+        TENSOR_N U G_H 3
+        "
+            .to_string(),
+        );
+        assert!(manual.is_ok());
+        assert!(expanded.is_ok());
+
+        let (_, manual) = execute_script_with_heap(manual.unwrap()).unwrap();
+        let (_, expanded) = execute_script_with_heap(expanded.unwrap()).unwrap();
+
+        assert_eq!(expanded.get("U").unwrap(), manual.get("U").unwrap());
+        assert_eq!(expanded.get("U").unwrap(), &hadamard_layer(5));
+    }
+
+    #[test]
+    fn test_seeded_execution() {
+        let script = "
+            INITIALIZE R 4
+            SUPERPOSE S 4
+            SELECT P S 0 2
+            MEASURE P RES1
+            MEASURE S RES2
+            APPLY (TENSOR (TENSOR G_H G_H) (TENSOR G_H G_H)) R
+            MEASURE R RES3
+        ";
+
+        let first = execute_script_seeded(parse(script.to_string()).unwrap(), 42).unwrap();
+        let second = execute_script_seeded(parse(script.to_string()).unwrap(), 42).unwrap();
+
+        for key in ["RES1", "RES2", "RES3"] {
+            assert_eq!(first.get(key).unwrap(), second.get(key).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_initialize_basis_state() {
+        let ast = parse(
+            "
+        INITIALIZE R 2 3
+        MEASURE R RES
+        "
+            .to_string(),
+        );
+        assert!(ast.is_ok());
+
+        let res = execute_script(ast.unwrap());
+
+        assert!(res.is_ok());
+
+        let res = res.unwrap();
+        assert_eq!(res.get("RES").unwrap().0, mat![c!(0); c!(0); c!(0); c!(1)]);
+        assert_eq!(res.get("RES").unwrap().1, "11");
+
+        let ast = parse("INITIALIZE R 2 4".to_string()).unwrap();
+        assert!(execute_script(ast).is_err());
+    }
+
+    #[test]
+    fn test_initialize_register_size() {
+        for (n, size) in [(1, 2), (2, 4), (3, 8), (5, 32)] {
+            let ast = parse(format!("INITIALIZE R {}", n)).unwrap();
+            let mut memory = QuantumMemory::new(StdRng::seed_from_u64(0));
+            execute_ast_node(&ast[0], &mut memory).unwrap();
+            assert_eq!(
+                unwrap_matrix(memory.heap.get("R").unwrap()).unwrap().size(),
+                (size, 1)
+            );
+        }
+
+        for n in ["-1", "100"] {
+            let ast = parse(format!("INITIALIZE R {}", n)).unwrap();
+            assert!(execute_script(ast).is_err(), "{}", n);
+        }
+    }
+
+    #[test]
+    fn test_fidelity_and_trace_distance() {
+        let ast = parse(
+            "
+        INITIALIZE A 2
+        INITIALIZE B 2
+        INITIALIZE C 2 3
+        FIDELITY A B SAME
+        FIDELITY A C ORTH
+        TRACE_DIST A B DIST_SAME
+        TRACE_DIST A C DIST_ORTH
+        "
+            .to_string(),
+        );
+        assert!(ast.is_ok());
+
+        let res = execute_script(ast.unwrap());
+
+        assert!(res.is_ok());
+
+        let res = res.unwrap();
+        assert!(res.get("SAME").unwrap().0.approx_eq(&mat![c!(1)], EPS));
+        assert_eq!(res.get("SAME").unwrap().1, "1.000000");
+        assert!(res.get("ORTH").unwrap().0.approx_eq(&mat![c!(0)], EPS));
+        assert!(res.get("DIST_SAME").unwrap().0.approx_eq(&mat![c!(0)], EPS));
+        assert!(res.get("DIST_ORTH").unwrap().0.approx_eq(&mat![c!(1)], EPS));
+    }
+
+    #[test]
+    fn test_approx_eq_trig_state() {
+        let ast = parse(
+            "
+        INITIALIZE R 1
+        APPLY G_H R
+        APPLY G_R_4 R
+        MEASURE R RES
+        "
+            .to_string(),
+        );
+        assert!(ast.is_ok());
+
+        let res = execute_script(ast.unwrap());
+
+        assert!(res.is_ok());
+
+        let res = res.unwrap();
+        let half_sqrt = 0.5_f64.sqrt();
+        let phase = PI / 4.0;
+        let expected = mat![
+            c!(half_sqrt);
+            c!(half_sqrt * phase.cos(), half_sqrt * phase.sin())
+        ];
+        assert!(res.get("RES").unwrap().0.approx_eq(&expected, EPS));
+    }
+
+    #[test]
+    fn test_integer_arithmetic() {
+        let ast = parse(
+            "
+        A ADD 3 2
+        B SUB A 4
+        C MUL A B
+        INITIALIZE R A
+        INITIALIZE S C
+        MEASURE R RES1
+        MEASURE S RES2
+        "
+            .to_string(),
+        );
+        assert!(ast.is_ok());
+
+        let res = execute_script(ast.unwrap());
+
+        assert!(res.is_ok());
+
+        let res = res.unwrap();
+        assert_eq!(res.get("RES1").unwrap().0.size(), (32, 1));
+        assert_eq!(res.get("RES1").unwrap().1, "00000");
+        assert_eq!(res.get("RES2").unwrap().0.size(), (32, 1));
+
+        let ast = parse("A ADD G_H 2".to_string()).unwrap();
+        assert!(execute_script(ast).is_err());
+    }
+
+    #[test]
+    fn test_controlled_x_prefab() {
+        let ast = parse(
+            "
+        INITIALIZE R 3 4
+        APPLY G_CX_0_2_3 R
+        MEASURE R RES
+        "
+            .to_string(),
+        );
+        assert!(ast.is_ok());
+
+        let res = execute_script(ast.unwrap());
+
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap().get("RES").unwrap().1, "101");
+
+        assert!(parse_literal(&"G_CX_1_1_2".to_string()).is_err());
+        assert!(parse_literal(&"G_CX_0_2_2".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_inline_matrix_literal() {
+        let ast = parse(
+            "
+        INITIALIZE R 1
+        APPLY G_MAT_0_1_1_0 R
+        MEASURE R RES
+        "
+            .to_string(),
+        );
+        assert!(ast.is_ok());
+
+        let res = execute_script(ast.unwrap());
+
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap().get("RES").unwrap().1, "1");
+
+        match parse_literal(&"G_MAT_0.5_0.5i_-0.5i_1-2i".to_string()) {
+            Ok(LiteralValue::Matrix(m)) => {
+                assert_eq!(m, mat!(c!(0.5), c!(0.0, 0.5); c!(0.0, -0.5), c!(1, -2)))
+            }
+            _ => panic!("Expected a matrix literal"),
+        }
+
+        assert!(parse_literal(&"G_MAT_1_0_0".to_string()).is_err());
+        assert!(parse_literal(&"G_MAT_1_x_0_1".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_qft_iqft() {
+        let ast = parse("INITIALIZE R 3 5\nQFT R".to_string()).unwrap();
+        let mut memory = QuantumMemory::new(StdRng::seed_from_u64(0));
+        for node in &ast {
+            execute_ast_node(node, &mut memory).unwrap();
+        }
+
+        // QFT of a basis state spreads it evenly over all basis states
+        let transformed = unwrap_matrix(memory.heap.get("R").unwrap()).unwrap();
+        let amplitude = 0.125_f64.sqrt();
+        for row in &transformed.data {
+            assert!(f64_equal(row[0].modulus(), amplitude));
+        }
+
+        let iqft = parse("IQFT R".to_string()).unwrap();
+        execute_ast_node(&iqft[0], &mut memory).unwrap();
+        assert_matrix_approx_eq!(
+            unwrap_matrix(memory.heap.get("R").unwrap()).unwrap(),
+            Matrix::from_basis_string("101").unwrap(),
+            1e-9
+        );
+
+        let ast = parse("M TENSOR G_H G_H\nQFT M".to_string()).unwrap();
+        assert!(execute_script(ast).is_err());
+    }
+
+    #[test]
+    fn test_hadamard_all() {
+        let ast = parse("INITIALIZE R 3\nHALL R".to_string()).unwrap();
+        let mut memory = QuantumMemory::new(StdRng::seed_from_u64(0));
+        for node in &ast {
+            execute_ast_node(node, &mut memory).unwrap();
+        }
+
+        assert_matrix_approx_eq!(
+            unwrap_matrix(memory.heap.get("R").unwrap()).unwrap(),
+            uniform_superposition(3),
+            1e-9
+        );
+    }
+
+    #[test]
+    fn test_pauli_prefabs() {
+        let ast = parse("INITIALIZE R 1\nAPPLY G_X R\nMEASURE R RES".to_string()).unwrap();
+        assert_eq!(execute_script(ast).unwrap().get("RES").unwrap().1, "1");
+
+        let ast =
+            parse("INITIALIZE R 1\nAPPLY G_Y R\nAPPLY G_Z R\nMEASURE R RES".to_string()).unwrap();
+        assert_eq!(execute_script(ast).unwrap().get("RES").unwrap().1, "1");
+
+        match parse_literal(&"G_Y".to_string()) {
+            Ok(LiteralValue::Matrix(m)) => assert_eq!(m, pauli_y()),
+            _ => panic!("Expected a matrix literal"),
+        }
+    }
+
+    #[test]
+    fn test_vector_initialize() {
+        let ast = parse("INITIALIZE R [1 0 0 0]\nMEASURE R RES".to_string()).unwrap();
+        assert_eq!(execute_script(ast).unwrap().get("RES").unwrap().1, "00");
+
+        let ast = parse("INITIALIZE R [1 1]".to_string()).unwrap();
+        let mut memory = QuantumMemory::new(StdRng::seed_from_u64(0));
+        execute_ast_node(&ast[0], &mut memory).unwrap();
+        assert_eq!(
+            unwrap_matrix(memory.heap.get("R").unwrap()).unwrap(),
+            &hadamard().multiply(&Matrix::from_basis_string("0").unwrap())
+        );
+
+        for vector in ["[1 0 0]", "[0 0]", "[1 x]"] {
+            let ast = parse(format!("INITIALIZE R {}", vector)).unwrap();
+            assert!(execute_script(ast).is_err(), "{}", vector);
+        }
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        for script in [
+            "U TENSOR G_H G_H\nINITIALIZE R 1\nAPPLY U R",
+            "INITIALIZE R 1\nAPPLY U R",
+            "INITIALIZE R G_H",
+            "SUPERPOSE R G_H",
+            "SELECT S G_H 0 1",
+            "INITIALIZE R 2\nSELECT S R -1 1\nMEASURE S RES",
+        ] {
+            let ast = parse(script.to_string()).unwrap();
+            assert!(
+                matches!(execute_script(ast), Err(RunTimeError::SyntaxError(_))),
+                "{}",
+                script
+            );
+        }
+    }
+
+    #[test]
+    fn test_runtime_error_line() {
+        let script = "INITIALIZE R 1\nU TENSOR G_H G_H\n\nAPPLY U R";
+        let err = execute_script(parse_numbered(script.to_string()).unwrap()).unwrap_err();
+        assert_eq!(err.line(), Some(4));
+        assert!(err.to_string().starts_with("line 4: Syntax error"));
+
+        // Without source lines the error is returned as is
+        let err = execute_script(parse(script.to_string()).unwrap()).unwrap_err();
+        assert!(matches!(err, RunTimeError::SyntaxError(_)));
+    }
+
+    #[test]
+    fn test_controlled() {
+        let ast = parse("CU CONTROLLED G_X".to_string()).unwrap();
+        let mut memory = QuantumMemory::new(StdRng::seed_from_u64(0));
+        execute_ast_node(&ast[0], &mut memory).unwrap();
+        assert_eq!(
+            unwrap_matrix(memory.heap.get("CU").unwrap()).unwrap(),
+            &cnot()
+        );
+
+        for gate in ["(CONTROLLED G_X)", "G_CNOT"] {
+            let script = format!("INITIALIZE R 2 2\nAPPLY {} R\nMEASURE R RES", gate);
+            let res = execute_script(parse(script).unwrap()).unwrap();
+            assert_eq!(res.get("RES").unwrap().1, "11", "{}", gate);
+        }
+
+        let ast = parse("CU CONTROLLED G_MAT_1_0_0_0_1_0_0_0_1".to_string()).unwrap();
+        assert!(execute_script(ast).is_err());
+    }
+
+    #[test]
+    fn test_repeat() {
+        let ast = parse("U TENSOR G_H G_H\nREPEAT 2 {\nU TENSOR U G_H\n}".to_string()).unwrap();
+        let mut memory = QuantumMemory::new(StdRng::seed_from_u64(0));
+        for (_, node) in expand_repeats(ast.into()).0 {
+            execute_ast_node(&node, &mut memory).unwrap();
+        }
+        assert_matrix_approx_eq!(
+            unwrap_matrix(memory.heap.get("U").unwrap()).unwrap(),
+            &hadamard_layer(4),
+            EPS
+        );
+
+        // Nested blocks multiply, a zero count skips the body entirely
+        let script = "
+        INITIALIZE R 1
+        REPEAT 2 {
+            REPEAT 3 {
+                APPLY G_X R
+            }
+            REPEAT 0 {
+                APPLY G_H R
+            }
+        }
+        MEASURE R RES
+        ";
+        let res = execute_script(parse_numbered(script.to_string()).unwrap()).unwrap();
+        assert_eq!(res.get("RES").unwrap().1, "0");
+
+        let err = execute_script(
+            parse_numbered("INITIALIZE R 1\nREPEAT 1 {\nAPPLY G_CNOT R\n}".to_string()).unwrap(),
+        )
+        .unwrap_err();
+        assert_eq!(err.line(), Some(3));
+    }
+
+    #[test]
+    fn test_print() {
+        let ast = parse_numbered("INITIALIZE R 1 1\nPRINT R\nN ADD 3 2\nPRINT N".to_string());
+        let (_, log) = execute_script_logged(ast.unwrap()).unwrap();
+        assert_eq!(
+            log,
+            vec![
+                ("R".to_string(), "0\n1".to_string()),
+                ("N".to_string(), "5".to_string())
+            ]
+        );
+
+        let ast = parse_numbered("PRINT R".to_string());
+        assert!(execute_script_logged(ast.unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_execute_with_heap() {
+        let ast = parse_numbered("U TENSOR G_H G_H\nN ADD 1 1\nINITIALIZE R 1".to_string());
+        let (measurements, heap) = execute_script_with_heap(ast.unwrap()).unwrap();
+        assert!(measurements.is_empty());
+        assert_eq!(heap.len(), 2);
+        assert_matrix_approx_eq!(heap.get("U").unwrap(), &hadamard().tensor(&hadamard()), EPS);
+        assert_eq!(heap.get("R").unwrap(), &mat![c!(1); c!(0)]);
+    }
+
+    #[test]
+    fn test_apply_requires_unitary() {
+        // The QFT is unitary but not Hermitian
+        let ast = parse("U INVERSE G_QFTI_2\nINITIALIZE R 2\nAPPLY U R\nMEASURE R RES".to_string());
+        assert!(execute_script(ast.unwrap()).is_ok());
+
+        let ast = parse("INITIALIZE R 1\nAPPLY G_MAT_2_0_0_0.5 R".to_string());
+        assert!(matches!(
+            execute_script(ast.unwrap()),
+            Err(RunTimeError::SyntaxError(_))
+        ));
+    }
+
+    #[test]
+    fn test_inverse() {
+        let ast = parse(
+            "
+        INITIALIZE R 1
+        U INVERSE G_MAT_0_1i_1_0
+        APPLY U R
+        MEASURE R RES
+        "
+            .to_string(),
+        );
+        assert!(ast.is_ok());
+        assert_eq!(
+            execute_script(ast.unwrap()).unwrap().get("RES").unwrap().1,
+            "1"
+        );
+
+        let ast = parse(
+            "
+        INITIALIZE R 1
+        U INVERSE G_MAT_1_2_2_4
+        "
+            .to_string(),
+        );
+        assert!(ast.is_ok());
+        assert!(execute_script(ast.unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_concat_vectors() {
+        let ast = parse("INITIALIZE R 1\nINITIALIZE S 1\nA CONCAT R S".to_string()).unwrap();
+        match execute_script(ast) {
+            Err(RunTimeError::SyntaxError(mess)) => assert!(mess.contains("use APPLY")),
+            _ => panic!("Expected CONCAT of vectors to fail"),
+        }
+    }
+
+    #[test]
+    fn test_duplicate_measurement_label() {
+        let ast = parse("INITIALIZE R 2\nMEASURE R RES\nMEASURE R RES".to_string()).unwrap();
+        assert!(execute_script(ast).is_err());
+    }
+
+    #[test]
+    fn test_apply_dimension_mismatch() {
+        let ast = parse("INITIALIZE R 2\nAPPLY G_H R\nMEASURE R RES".to_string()).unwrap();
+        assert!(execute_script(ast).is_err());
+
+        let ast = parse("U TENSOR G_H G_H\nINITIALIZE R 1\nAPPLY U R".to_string()).unwrap();
+        assert!(execute_script_seeded(ast, 0).is_err());
+    }
+
+    #[test]
+    fn test_matrix_error_message() {
+        let e = matrix_error("APPLY", MatrixError::DimensionMismatch((4, 4), (2, 1)));
+        match e {
+            RunTimeError::SyntaxError(mess) => assert_eq!(
+                mess,
+                "APPLY failed: Dimension mismatch between a 4x4 and a 2x1 matrix"
+            ),
+            _ => panic!("Expected a syntax error"),
+        }
+    }
 }
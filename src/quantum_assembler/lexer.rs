@@ -9,6 +9,12 @@ pub enum TokenType {
     OpenBracket,
     CloseBracket,
 
+    OpenParen,
+    CloseParen,
+
+    OpenBrace,
+    CloseBrace,
+
     NewLine,
 }
 
@@ -18,17 +24,33 @@ pub struct Token {
     pub value: String,
 }
 
+// e.g. G_Uf_7_15 is the prefix G_Uf_ followed by 2 numbers
+fn is_numbered_prefab(token: &str, prefix: &str, n_params: usize) -> bool {
+    match token.strip_prefix(prefix) {
+        Some(params) => {
+            let params: Vec<&str> = params.split('_').collect();
+            params.len() == n_params && params.iter().all(|p| p.parse::<usize>().is_ok())
+        }
+        None => false,
+    }
+}
+
 fn match_token_type(token: &String) -> TokenType {
     match token.as_str() {
-        "INITIALIZE" | "MEASURE" | "SELECT" | "APPLY" | "CONCAT" | "TENSOR" | "INVERSE" => {
-            TokenType::Action
-        }
-        "G_H" | "G_CNOT" => TokenType::Prefabs,
+        "INITIALIZE" | "SUPERPOSE" | "MEASURE" | "SELECT" | "APPLY" | "CONCAT" | "TENSOR"
+        | "TENSOR_N" | "INVERSE" | "FIDELITY" | "TRACE_DIST" | "ADD" | "SUB" | "MUL" | "QFT"
+        | "IQFT" | "HALL" | "CONTROLLED" | "REPEAT" | "PRINT" => TokenType::Action,
+        "G_H" | "G_CNOT" | "G_X" | "G_Y" | "G_Z" => TokenType::Prefabs,
         _ => {
-            if token.starts_with("G_I_") || token.starts_with("G_R_") || token.starts_with("G_Uf_") || token.starts_with("G_QFTI_") {
+            if is_numbered_prefab(token, "G_I_", 1)
+                || is_numbered_prefab(token, "G_R_", 1)
+                || is_numbered_prefab(token, "G_Uf_", 2)
+                || is_numbered_prefab(token, "G_QFTI_", 1)
+                || is_numbered_prefab(token, "G_CX_", 3)
+                || token.starts_with("G_MAT_")
+            {
                 TokenType::Prefabs
-            } else
-            if token.parse::<i32>().is_ok() {
+            } else if token.parse::<i32>().is_ok() {
                 TokenType::Literal
             } else {
                 TokenType::Identifier
@@ -83,6 +105,38 @@ pub fn tokenize(inp: String) -> Vec<Token> {
                     value: "]".to_string(),
                 });
             }
+            '(' => {
+                push_current_token(&mut tokens, &mut current_token);
+
+                tokens.push(Token {
+                    token_type: TokenType::OpenParen,
+                    value: "(".to_string(),
+                });
+            }
+            ')' => {
+                push_current_token(&mut tokens, &mut current_token);
+
+                tokens.push(Token {
+                    token_type: TokenType::CloseParen,
+                    value: ")".to_string(),
+                });
+            }
+            '{' => {
+                push_current_token(&mut tokens, &mut current_token);
+
+                tokens.push(Token {
+                    token_type: TokenType::OpenBrace,
+                    value: "{".to_string(),
+                });
+            }
+            '}' => {
+                push_current_token(&mut tokens, &mut current_token);
+
+                tokens.push(Token {
+                    token_type: TokenType::CloseBrace,
+                    value: "}".to_string(),
+                });
+            }
             _ => {
                 current_token.push(c);
             }
@@ -161,6 +215,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_numbered_prefabs() {
+        for prefab in ["G_R_16", "G_I_32", "G_QFTI_3", "G_Uf_7_15", "G_CX_0_2_3"] {
+            assert_eq!(
+                tokenize(prefab.to_string())[0].token_type,
+                TokenType::Prefabs,
+                "{}",
+                prefab
+            );
+        }
+
+        for identifier in ["G_I_", "G_R_x", "G_Uf_7", "G_CX_0_1"] {
+            assert_eq!(
+                tokenize(identifier.to_string())[0].token_type,
+                TokenType::Identifier,
+                "{}",
+                identifier
+            );
+        }
+    }
+
     #[test]
     fn test_bit_array() {
         let inp = "INITIALIZE R2 [0 0 ]";
@@ -188,4 +263,48 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_parens() {
+        let inp = "APPLY (TENSOR G_H G_H) R";
+        let tokens = tokenize(inp.to_string());
+        assert_eq!(tokens.len(), 7);
+        assert_eq!(
+            tokens[1],
+            Token {
+                token_type: TokenType::OpenParen,
+                value: "(".to_string()
+            }
+        );
+        assert_eq!(
+            tokens[2],
+            Token {
+                token_type: TokenType::Action,
+                value: "TENSOR".to_string()
+            }
+        );
+        assert_eq!(
+            tokens[5],
+            Token {
+                token_type: TokenType::CloseParen,
+                value: ")".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_block_tokens() {
+        let tokens = tokenize("REPEAT 2 {\n}".to_string());
+        let types: Vec<TokenType> = tokens.iter().map(|t| t.token_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::Action,
+                TokenType::Literal,
+                TokenType::OpenBrace,
+                TokenType::NewLine,
+                TokenType::CloseBrace,
+            ]
+        );
+    }
 }
@@ -1,8 +1,9 @@
 use std::collections::HashMap;
 
+pub mod circuit;
 mod lexer;
 mod parser;
-mod quantum_sim;
+pub mod quantum_sim;
 
 mod executor;
 
@@ -12,18 +13,104 @@ pub enum QuantumSimError {
     ParseError(parser::ParseError),
 }
 
+impl From<parser::ParseError> for QuantumSimError {
+    fn from(e: parser::ParseError) -> QuantumSimError {
+        QuantumSimError::ParseError(e)
+    }
+}
+
+impl From<executor::RunTimeError> for QuantumSimError {
+    fn from(e: executor::RunTimeError) -> QuantumSimError {
+        QuantumSimError::RuntimeError(e)
+    }
+}
+
 pub fn run(
     input: String,
 ) -> Result<HashMap<String, (crate::matrix::matrix::Matrix, String)>, QuantumSimError> {
-    let ast = parser::parse(input);
-    if ast.is_err() {
-        return Err(QuantumSimError::ParseError(ast.err().unwrap()));
+    let ast = parser::parse_numbered(input)?;
+    Ok(executor::execute_script(ast)?)
+}
+
+pub fn run_timed(
+    input: String,
+) -> Result<(executor::Measurements, executor::Timings), QuantumSimError> {
+    let ast = parser::parse_numbered(input)?;
+    Ok(executor::execute_script_timed(ast)?)
+}
+
+pub fn run_logged(
+    input: String,
+) -> Result<(executor::Measurements, executor::PrintLog), QuantumSimError> {
+    let ast = parser::parse_numbered(input)?;
+    Ok(executor::execute_script_logged(ast)?)
+}
+
+pub fn run_with_heap(
+    input: String,
+) -> Result<(executor::Measurements, executor::MatrixHeap), QuantumSimError> {
+    let ast = parser::parse_numbered(input)?;
+    Ok(executor::execute_script_with_heap(ast)?)
+}
+
+pub fn run_seeded(input: String, seed: u64) -> Result<executor::Measurements, QuantumSimError> {
+    let ast = parser::parse_numbered(input)?;
+    Ok(executor::execute_script_seeded(ast, seed)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_seeded() {
+        let script = "
+            SUPERPOSE R 3
+            MEASURE R RES1
+            SUPERPOSE R 3
+            MEASURE R RES2
+        ";
+
+        let first = run_seeded(script.to_string(), 7).unwrap();
+        let second = run_seeded(script.to_string(), 7).unwrap();
+
+        assert_eq!(first.get("RES1").unwrap().1, second.get("RES1").unwrap().1);
+        assert_eq!(first.get("RES2").unwrap().1, second.get("RES2").unwrap().1);
+    }
+
+    #[test]
+    fn test_run_errors() {
+        assert!(matches!(
+            run("INITIALIZE R 2 3 4 5 6".to_string()),
+            Err(QuantumSimError::ParseError(_))
+        ));
+        assert!(matches!(
+            run("INITIALIZE R 2\nAPPLY G_H R".to_string()),
+            Err(QuantumSimError::RuntimeError(_))
+        ));
+        assert!(matches!(
+            run_seeded("INITIALIZE R 1\nAPPLY G_CNOT R".to_string(), 0),
+            Err(QuantumSimError::RuntimeError(_))
+        ));
     }
 
-    let result = executor::execute_script(ast.unwrap());
-    if result.is_err() {
-        return Err(QuantumSimError::RuntimeError(result.err().unwrap()));
+    #[test]
+    fn test_run_hadamard() {
+        let res = run("INITIALIZE R 2\nU TENSOR G_H G_H\nAPPLY U R\nMEASURE R RES".to_string());
+        let outcome = res.unwrap().get("RES").unwrap().1.clone();
+        assert!(["00", "01", "10", "11"].contains(&outcome.as_str()));
+
+        // H is its own inverse, so applying it twice is deterministic
+        let res = run("INITIALIZE R 1\nAPPLY G_H R\nAPPLY G_H R\nMEASURE R RES".to_string());
+        assert_eq!(res.unwrap().get("RES").unwrap().1, "0");
     }
 
-    Ok(result.unwrap())
+    #[test]
+    fn test_run_error_lines() {
+        let err = run("INITIALIZE R 2\nAPPLY G_H R".to_string()).unwrap_err();
+        assert!(matches!(err, QuantumSimError::RuntimeError(ref e) if e.line() == Some(2)));
+
+        let err = run("INITIALIZE R 2\n\nFOO BAR BAZ QUX".to_string()).unwrap_err();
+        assert!(matches!(err, QuantumSimError::ParseError(ref e) if e.line() == Some(3)));
+    }
 }
@@ -15,14 +15,69 @@ pub enum ASTNode {
     VariableAssignment(String, MemoryLocation, Rc<ASTNode>),
 
     FunctionApplication(String, Vec<ASTNode>),
+    Repeat(usize, NumberedAST),
 }
 
 pub type AST = Vec<ASTNode>;
 
+// Source-like rendering of a single node, e.g. `U = TENSOR(G_H, G_H)`
+impl fmt::Display for ASTNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ASTNode::Literal(val) | ASTNode::Identifier(val) => write!(f, "{}", val),
+            ASTNode::VariableAssignment(var_name, _, val) => write!(f, "{} = {}", var_name, val),
+            ASTNode::FunctionApplication(func, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(f, "{}({})", func, args)
+            }
+            ASTNode::Repeat(count, body) => {
+                let body = body
+                    .0
+                    .iter()
+                    .map(|(_, node)| node.to_string())
+                    .collect::<Vec<String>>()
+                    .join("; ");
+                write!(f, "REPEAT {} {{ {} }}", count, body)
+            }
+        }
+    }
+}
+
+// Parsed script where every node keeps the 1-based source line it came from, if known
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumberedAST(pub Vec<(Option<usize>, ASTNode)>);
+
+impl From<AST> for NumberedAST {
+    fn from(ast: AST) -> NumberedAST {
+        NumberedAST(ast.into_iter().map(|node| (None, node)).collect())
+    }
+}
+
 #[derive(Debug)]
 pub enum ParseError {
     SyntaxError(String), // TOO GENERIC
     NotImplemented,
+    Line(usize, Box<ParseError>),
+}
+
+impl ParseError {
+    pub fn at_line(self, line: usize) -> ParseError {
+        match self {
+            ParseError::Line(_, _) => self,
+            _ => ParseError::Line(line, Box::new(self)),
+        }
+    }
+
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            ParseError::Line(line, _) => Some(*line),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for ParseError {
@@ -30,6 +85,7 @@ impl fmt::Display for ParseError {
         match self {
             ParseError::SyntaxError(mess) => write!(f, "Syntax error: {}", mess),
             ParseError::NotImplemented => write!(f, "Not implemented"),
+            ParseError::Line(line, err) => write!(f, "line {}: {}", line, err),
         }
     }
 }
@@ -39,50 +95,161 @@ impl error::Error for ParseError {
         match self {
             ParseError::SyntaxError(_) => "Syntax error in code",
             ParseError::NotImplemented => "Not implemented",
+            ParseError::Line(_, _) => "Error in code at a given line",
         }
     }
 }
 
-fn parse_param(param: &Token) -> Result<ASTNode, ParseError> {
-    match param.token_type {
-        TokenType::Literal => Ok(ASTNode::Literal(param.value.clone())),
-        TokenType::Prefabs => Ok(ASTNode::Literal(param.value.clone())),
-        TokenType::Identifier => Ok(ASTNode::Identifier(param.value.clone())),
+fn tokens_to_string(inp: &[Token]) -> String {
+    inp.iter()
+        .map(|i| i.value.clone())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// Splits a line into parameter units: a single token or a full parenthesized group
+fn group_params(inp: &[Token]) -> Result<Vec<Vec<Token>>, ParseError> {
+    let mut groups: Vec<Vec<Token>> = vec![];
+    let mut current: Vec<Token> = vec![];
+    let mut depth = 0;
+
+    for token in inp {
+        match token.token_type {
+            TokenType::OpenParen => depth += 1,
+            TokenType::CloseParen => {
+                if depth == 0 {
+                    return Err(ParseError::SyntaxError(format!(
+                        "Unexpected closing parenthesis: {}",
+                        tokens_to_string(inp)
+                    )));
+                }
+                depth -= 1;
+            }
+            _ => {}
+        }
+
+        current.push(token.clone());
+        if depth == 0 {
+            groups.push(current);
+            current = vec![];
+        }
+    }
+
+    if depth != 0 {
+        return Err(ParseError::SyntaxError(format!(
+            "Unclosed parenthesis: {}",
+            tokens_to_string(inp)
+        )));
+    }
+
+    Ok(groups)
+}
+
+fn parse_nested_application(inp: &[Token]) -> Result<ASTNode, ParseError> {
+    let groups = group_params(inp)?;
+
+    match groups.split_first() {
+        Some((action, params))
+            if action.len() == 1 && action[0].token_type == TokenType::Action =>
+        {
+            Ok(ASTNode::FunctionApplication(
+                action[0].value.clone(),
+                params
+                    .iter()
+                    .map(|p| parse_param(p))
+                    .collect::<Result<Vec<ASTNode>, ParseError>>()?,
+            ))
+        }
         _ => Err(ParseError::SyntaxError(format!(
-            "Invalid paramater {} - {:?}",
-            param.value, param.token_type
+            "Invalid nested expression: ({})",
+            tokens_to_string(inp)
+        ))),
+    }
+}
+
+fn parse_param(param: &[Token]) -> Result<ASTNode, ParseError> {
+    match param {
+        [token] => match token.token_type {
+            TokenType::Literal => Ok(ASTNode::Literal(token.value.clone())),
+            TokenType::Prefabs => Ok(ASTNode::Literal(token.value.clone())),
+            TokenType::Identifier => Ok(ASTNode::Identifier(token.value.clone())),
+            _ => Err(ParseError::SyntaxError(format!(
+                "Invalid paramater {} - {:?}",
+                token.value, token.token_type
+            ))),
+        },
+        [open, inner @ .., close]
+            if open.token_type == TokenType::OpenParen
+                && close.token_type == TokenType::CloseParen =>
+        {
+            parse_nested_application(inner)
+        } // e.g. (TENSOR G_H G_H)
+        _ => Err(ParseError::SyntaxError(format!(
+            "Invalid paramater {}",
+            tokens_to_string(param)
+        ))),
+    }
+}
+
+fn parse_target(target: &[Token]) -> Result<String, ParseError> {
+    match target {
+        [token] if token.token_type == TokenType::Identifier => Ok(token.value.clone()),
+        _ => Err(ParseError::SyntaxError(format!(
+            "Invalid assignment target {}",
+            tokens_to_string(target)
+        ))),
+    }
+}
+
+fn parse_single_token_group(action: &Token, param0: &[Token]) -> Result<ASTNode, ParseError> {
+    match action.value.as_str() {
+        "QFT" | "IQFT" | "HALL" => Ok(ASTNode::VariableAssignment(
+            parse_target(param0)?,
+            MemoryLocation::Heap,
+            Rc::new(ASTNode::FunctionApplication(
+                action.value.clone(),
+                vec![parse_param(param0)?],
+            )),
+        )),
+        "PRINT" => Ok(ASTNode::FunctionApplication(
+            action.value.clone(),
+            vec![parse_param(param0)?],
+        )),
+        _ => Err(ParseError::SyntaxError(format!(
+            "Invalid single action {} - {:?}",
+            action.value, action.token_type
         ))),
     }
 }
 
 fn parse_dual_token_group(
     action: &Token,
-    param0: &Token,
-    param1: &Token,
+    param0: &[Token],
+    param1: &[Token],
 ) -> Result<ASTNode, ParseError> {
     match action.value.as_str() {
         "APPLY" => Ok(ASTNode::VariableAssignment(
-            param1.value.clone(),
+            parse_target(param1)?,
             MemoryLocation::Heap,
             Rc::new(ASTNode::FunctionApplication(
                 action.value.clone(),
-                vec![parse_param(param0).unwrap(), parse_param(param1).unwrap()],
+                vec![parse_param(param0)?, parse_param(param1)?],
             )),
         )),
-        "INITIALIZE" => Ok(ASTNode::VariableAssignment(
-            param0.value.clone(),
+        "INITIALIZE" | "SUPERPOSE" => Ok(ASTNode::VariableAssignment(
+            parse_target(param0)?,
             MemoryLocation::Heap,
             Rc::new(ASTNode::FunctionApplication(
                 action.value.clone(),
-                vec![parse_param(param1).unwrap()],
+                vec![parse_param(param1)?],
             )),
         )),
         "MEASURE" => Ok(ASTNode::VariableAssignment(
-            param1.value.clone(),
+            parse_target(param1)?,
             MemoryLocation::Measurement,
             Rc::new(ASTNode::FunctionApplication(
                 action.value.clone(),
-                vec![parse_param(param0).unwrap()],
+                vec![parse_param(param0)?],
             )),
         )),
         _ => Err(ParseError::SyntaxError(format!(
@@ -92,23 +259,53 @@ fn parse_dual_token_group(
     }
 }
 
+fn parse_triple_token_group(
+    action: &Token,
+    param0: &[Token],
+    param1: &[Token],
+    param2: &[Token],
+) -> Result<ASTNode, ParseError> {
+    match action.value.as_str() {
+        "INITIALIZE" => Ok(ASTNode::VariableAssignment(
+            parse_target(param0)?,
+            MemoryLocation::Heap,
+            Rc::new(ASTNode::FunctionApplication(
+                action.value.clone(),
+                vec![parse_param(param1)?, parse_param(param2)?],
+            )),
+        )),
+        "FIDELITY" | "TRACE_DIST" => Ok(ASTNode::VariableAssignment(
+            parse_target(param2)?,
+            MemoryLocation::Measurement,
+            Rc::new(ASTNode::FunctionApplication(
+                action.value.clone(),
+                vec![parse_param(param0)?, parse_param(param1)?],
+            )),
+        )),
+        _ => Err(ParseError::SyntaxError(format!(
+            "Invalid triple action {} - {:?}",
+            action.value, action.token_type
+        ))),
+    }
+}
+
 fn parse_quat_token_group(
     action: &Token,
-    param0: &Token,
-    param1: &Token,
-    param2: &Token,
-    param3: &Token,
+    param0: &[Token],
+    param1: &[Token],
+    param2: &[Token],
+    param3: &[Token],
 ) -> Result<ASTNode, ParseError> {
     match action.value.as_str() {
         "SELECT" => Ok(ASTNode::VariableAssignment(
-            param0.value.clone(),
+            parse_target(param0)?,
             MemoryLocation::Heap,
             Rc::new(ASTNode::FunctionApplication(
                 action.value.clone(),
                 vec![
-                    parse_param(param1).unwrap(),
-                    parse_param(param2).unwrap(),
-                    parse_param(param3).unwrap(),
+                    parse_param(param1)?,
+                    parse_param(param2)?,
+                    parse_param(param3)?,
                 ],
             )),
         )),
@@ -121,16 +318,16 @@ fn parse_quat_token_group(
 
 fn parse_ass_single_token_group(
     action: &Token,
-    ass: &Token,
-    param1: &Token,
+    ass: &[Token],
+    param1: &[Token],
 ) -> Result<ASTNode, ParseError> {
     match action.value.as_str() {
-        "INVERSE" => Ok(ASTNode::VariableAssignment(
-            ass.value.clone(),
+        "INVERSE" | "CONTROLLED" => Ok(ASTNode::VariableAssignment(
+            parse_target(ass)?,
             MemoryLocation::Heap,
             Rc::new(ASTNode::FunctionApplication(
                 action.value.clone(),
-                vec![parse_param(param1).unwrap()],
+                vec![parse_param(param1)?],
             )),
         )),
         _ => Err(ParseError::SyntaxError(format!(
@@ -142,17 +339,17 @@ fn parse_ass_single_token_group(
 
 fn parse_ass_dual_token_group(
     action: &Token,
-    ass: &Token,
-    param1: &Token,
-    param2: &Token,
+    ass: &[Token],
+    param1: &[Token],
+    param2: &[Token],
 ) -> Result<ASTNode, ParseError> {
     match action.value.as_str() {
-        "TENSOR" | "CONCAT" => Ok(ASTNode::VariableAssignment(
-            ass.value.clone(),
+        "TENSOR" | "CONCAT" | "ADD" | "SUB" | "MUL" => Ok(ASTNode::VariableAssignment(
+            parse_target(ass)?,
             MemoryLocation::Heap,
             Rc::new(ASTNode::FunctionApplication(
                 action.value.clone(),
-                vec![parse_param(param1).unwrap(), parse_param(param2).unwrap()],
+                vec![parse_param(param1)?, parse_param(param2)?],
             )),
         )),
         _ => Err(ParseError::SyntaxError(format!(
@@ -162,19 +359,18 @@ fn parse_ass_dual_token_group(
     }
 }
 
-fn parse_vector_init(ass: &Token, params: &Vec<Token>) -> Result<ASTNode, ParseError> {
+fn parse_vector_init(ass: &[Token], params: &[Vec<Token>]) -> Result<ASTNode, ParseError> {
     let res = ASTNode::VariableAssignment(
-        ass.value.clone(),
+        parse_target(ass)?,
         MemoryLocation::Heap,
         Rc::new(ASTNode::FunctionApplication(
             "INITIALIZE".to_string(),
             vec![ASTNode::FunctionApplication(
                 "VECTOR".to_string(),
                 params
-                    .clone()
                     .iter()
-                    .map(|p| parse_param(&p).unwrap())
-                    .collect::<Vec<ASTNode>>(),
+                    .map(|p| parse_param(p))
+                    .collect::<Result<Vec<ASTNode>, ParseError>>()?,
             )],
         )),
     );
@@ -183,46 +379,149 @@ fn parse_vector_init(ass: &Token, params: &Vec<Token>) -> Result<ASTNode, ParseE
 }
 
 fn parse_token_group(inp: Vec<Token>) -> Result<ASTNode, ParseError> {
-    let type_vec: Vec<TokenType> = inp.iter().map(|t| t.token_type).collect();
+    let groups = group_params(&inp)?;
+    let type_vec: Vec<TokenType> = groups.iter().map(|g| g[0].token_type).collect();
     match type_vec.as_slice() {
-        [TokenType::Action, _, _] => parse_dual_token_group(&inp[0], &inp[1], &inp[2]), // e.g APPLY U R
+        [TokenType::Action, _] => parse_single_token_group(&groups[0][0], &groups[1]), // e.g QFT R
+        [TokenType::Action, _, _] => parse_dual_token_group(&groups[0][0], &groups[1], &groups[2]), // e.g APPLY U R
         [TokenType::Action, TokenType::Identifier, TokenType::OpenBracket, .., TokenType::CloseBracket] => {
-            parse_vector_init(&inp[1], &inp[3..(inp.len() - 1)].to_vec())
+            parse_vector_init(&groups[1], &groups[3..(groups.len() - 1)])
         } // e.g INITIALIZE R [1, 2, 3]
-        [TokenType::Action, _, _, _, _] => {
-            parse_quat_token_group(&inp[0], &inp[1], &inp[2], &inp[3], &inp[4])
-        } // e.g SELECT S1 R1 2 3
+        [TokenType::Action, _, _, _] => {
+            parse_triple_token_group(&groups[0][0], &groups[1], &groups[2], &groups[3])
+        } // e.g. INITIALIZE R 2 3
+        [TokenType::Action, _, _, _, _] => parse_quat_token_group(
+            &groups[0][0],
+            &groups[1],
+            &groups[2],
+            &groups[3],
+            &groups[4],
+        ), // e.g SELECT S1 R1 2 3
         [TokenType::Identifier, TokenType::Action, _] => {
-            parse_ass_single_token_group(&inp[1], &inp[0], &inp[2])
+            parse_ass_single_token_group(&groups[1][0], &groups[0], &groups[2])
         } // e.g U2 INVERSE U1
         [TokenType::Identifier, TokenType::Action, _, _] => {
-            parse_ass_dual_token_group(&inp[1], &inp[0], &inp[2], &inp[3])
+            parse_ass_dual_token_group(&groups[1][0], &groups[0], &groups[2], &groups[3])
         } // e.g. R2 TENSOR U1 U2
         _ => Err(ParseError::SyntaxError(format!(
             "Invalid action pattern: {}",
-            inp.iter()
-                .map(|i| i.value.clone())
-                .collect::<Vec<String>>()
-                .join(" ")
+            tokens_to_string(&inp)
         ))),
     }
 }
 
-pub fn parse(inp: String) -> Result<Vec<ASTNode>, ParseError> {
+// TENSOR_N U BASE N expands to N successive `U TENSOR U BASE` assignments
+fn expand_tensor_n(
+    ass: &[Token],
+    base: &[Token],
+    count: &[Token],
+) -> Result<Vec<ASTNode>, ParseError> {
+    let n = match count {
+        [token] if token.token_type == TokenType::Literal => {
+            token.value.parse::<usize>().map_err(|_| {
+                ParseError::SyntaxError(format!("Invalid TENSOR_N count {}", token.value))
+            })?
+        }
+        _ => {
+            return Err(ParseError::SyntaxError(format!(
+                "Invalid TENSOR_N count {}",
+                tokens_to_string(count)
+            )))
+        }
+    };
+
+    let target = parse_target(ass)?;
+    let base = parse_param(base)?;
+
+    Ok((0..n)
+        .map(|_| {
+            ASTNode::VariableAssignment(
+                target.clone(),
+                MemoryLocation::Heap,
+                Rc::new(ASTNode::FunctionApplication(
+                    "TENSOR".to_string(),
+                    vec![ASTNode::Identifier(target.clone()), base.clone()],
+                )),
+            )
+        })
+        .collect())
+}
+
+// Meta-instructions expand to multiple nodes, every other line to a single node
+fn parse_line(inp: Vec<Token>) -> Result<Vec<ASTNode>, ParseError> {
+    let groups = group_params(&inp)?;
+    match groups.as_slice() {
+        [action, ass, base, count] if action[0].value == "TENSOR_N" => {
+            expand_tensor_n(ass, base, count)
+        } // e.g. TENSOR_N U G_H 3
+        _ => Ok(vec![parse_token_group(inp)?]),
+    }
+}
+
+fn parse_repeat_count(count: &Token) -> Result<usize, ParseError> {
+    match count.token_type {
+        TokenType::Literal => count
+            .value
+            .parse::<usize>()
+            .map_err(|_| ParseError::SyntaxError(format!("Invalid REPEAT count {}", count.value))),
+        _ => Err(ParseError::SyntaxError(format!(
+            "Invalid REPEAT count {}",
+            count.value
+        ))),
+    }
+}
+
+pub fn parse_numbered(inp: String) -> Result<NumberedAST, ParseError> {
     let tokens = tokenize(inp);
 
-    // TODO SPLIT BY NEWLINE
-    // MATCH EXPRESSION AND PARSE
-    let groups: Vec<&[Token]> = tokens
+    // Open REPEAT blocks, innermost last: (count, opening line, body)
+    let mut blocks: Vec<(usize, usize, NumberedAST)> = vec![];
+    let mut res = vec![];
+
+    // Enumerate before dropping empty lines so numbers match the source
+    for (i, line) in tokens
         .split(|t| t.token_type == TokenType::NewLine)
-        .filter(|g| g.len() > 0)
-        .collect();
+        .enumerate()
+        .filter(|(_, g)| !g.is_empty())
+    {
+        let line_nr = i + 1;
+        match line {
+            [repeat, count, open]
+                if repeat.value == "REPEAT" && open.token_type == TokenType::OpenBrace =>
+            {
+                let count = parse_repeat_count(count).map_err(|e| e.at_line(line_nr))?;
+                blocks.push((count, line_nr, NumberedAST(vec![])));
+            } // e.g. REPEAT 3 {
+            [close] if close.token_type == TokenType::CloseBrace => {
+                let (count, start, body) = blocks.pop().ok_or_else(|| {
+                    ParseError::SyntaxError("Unexpected closing brace".to_string()).at_line(line_nr)
+                })?;
+                let target = blocks.last_mut().map_or(&mut res, |block| &mut block.2 .0);
+                target.push((Some(start), ASTNode::Repeat(count, body)));
+            }
+            _ => {
+                let nodes = parse_line(line.to_vec()).map_err(|e| e.at_line(line_nr))?;
+                let target = blocks.last_mut().map_or(&mut res, |block| &mut block.2 .0);
+                target.extend(nodes.into_iter().map(|node| (Some(line_nr), node)));
+            }
+        }
+    }
+
+    if let Some((_, start, _)) = blocks.last() {
+        return Err(ParseError::SyntaxError("Unclosed REPEAT block".to_string()).at_line(*start));
+    }
+
+    Ok(NumberedAST(res))
+}
 
-    let res: Vec<ASTNode> = groups
+// Drops the line numbers, the tests compare against plain node lists
+#[cfg(test)]
+pub fn parse(inp: String) -> Result<Vec<ASTNode>, ParseError> {
+    Ok(parse_numbered(inp)?
+        .0
         .into_iter()
-        .map(|g| parse_token_group(g.to_vec()).unwrap())
-        .collect();
-    Ok(res)
+        .map(|(_, node)| node)
+        .collect())
 }
 
 #[cfg(test)]
@@ -413,4 +712,185 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_parse_nested_application() {
+        let input = "APPLY (TENSOR G_H (TENSOR G_H G_H)) R".to_string();
+        let res = parse(input);
+
+        assert!(res.is_ok());
+        assert_eq!(
+            res.unwrap(),
+            vec![ASTNode::VariableAssignment(
+                "R".to_string(),
+                MemoryLocation::Heap,
+                Rc::new(ASTNode::FunctionApplication(
+                    "APPLY".to_string(),
+                    vec![
+                        ASTNode::FunctionApplication(
+                            "TENSOR".to_string(),
+                            vec![
+                                ASTNode::Literal("G_H".to_string()),
+                                ASTNode::FunctionApplication(
+                                    "TENSOR".to_string(),
+                                    vec![
+                                        ASTNode::Literal("G_H".to_string()),
+                                        ASTNode::Literal("G_H".to_string())
+                                    ]
+                                )
+                            ]
+                        ),
+                        ASTNode::Identifier("R".to_string())
+                    ]
+                ))
+            )]
+        );
+    }
+
+    #[test]
+    fn test_parse_unbalanced_parens() {
+        let unclosed = tokenize("APPLY (TENSOR G_H G_H R".to_string());
+        assert!(parse_token_group(unclosed).is_err());
+
+        let unopened = tokenize("APPLY TENSOR G_H G_H) R".to_string());
+        assert!(parse_token_group(unopened).is_err());
+    }
+
+    #[test]
+    fn test_parse_tensor_n() {
+        let res = parse("TENSOR_N U G_H 3".to_string());
+
+        assert!(res.is_ok());
+
+        let tensor = ASTNode::VariableAssignment(
+            "U".to_string(),
+            MemoryLocation::Heap,
+            Rc::new(ASTNode::FunctionApplication(
+                "TENSOR".to_string(),
+                vec![
+                    ASTNode::Identifier("U".to_string()),
+                    ASTNode::Literal("G_H".to_string()),
+                ],
+            )),
+        );
+        assert_eq!(res.unwrap(), vec![tensor.clone(), tensor.clone(), tensor]);
+
+        let res = parse("TENSOR_N U G_H 0".to_string());
+        assert_eq!(res.unwrap(), vec![]);
+
+        let res = parse_line(tokenize("TENSOR_N U G_H X".to_string()));
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_parse_initialize_basis_state() {
+        let res = parse("INITIALIZE R 3 5".to_string());
+
+        assert!(res.is_ok());
+        assert_eq!(
+            res.unwrap(),
+            vec![ASTNode::VariableAssignment(
+                "R".to_string(),
+                MemoryLocation::Heap,
+                Rc::new(ASTNode::FunctionApplication(
+                    "INITIALIZE".to_string(),
+                    vec![
+                        ASTNode::Literal("3".to_string()),
+                        ASTNode::Literal("5".to_string())
+                    ]
+                ))
+            )]
+        );
+    }
+
+    #[test]
+    fn test_display_ast_node() {
+        let node = ASTNode::VariableAssignment(
+            "U".to_string(),
+            MemoryLocation::Heap,
+            Rc::new(ASTNode::FunctionApplication(
+                "TENSOR".to_string(),
+                vec![
+                    ASTNode::Literal("G_H".to_string()),
+                    ASTNode::Literal("G_H".to_string()),
+                ],
+            )),
+        );
+        assert_eq!(node.to_string(), "U = TENSOR(G_H, G_H)");
+
+        let res = parse("INITIALIZE R 2\nMEASURE R RES".to_string()).unwrap();
+        assert_eq!(res[0].to_string(), "R = INITIALIZE(2)");
+        assert_eq!(res[1].to_string(), "RES = MEASURE(R)");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("FOO BAR BAZ QUX".to_string()).is_err());
+        // Invalid parameters inside an otherwise valid action
+        assert!(parse("APPLY (G_H R".to_string()).is_err());
+        assert!(parse("INITIALIZE R ((2))".to_string()).is_err());
+        assert!(parse("R INITIALIZE [1 (0]".to_string()).is_err());
+        // A single bad line fails the whole script
+        assert!(parse("INITIALIZE R 2\nFOO BAR BAZ QUX\nMEASURE R RES".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_parse_error_line() {
+        let err = parse("INITIALIZE R 2\nAPPLY G_H R\nFOO BAR BAZ QUX\nMEASURE R RES".to_string())
+            .unwrap_err();
+        assert_eq!(err.line(), Some(3));
+        assert!(err.to_string().starts_with("line 3: Syntax error"));
+
+        // Blank lines still count towards the line number
+        let err = parse("\nINITIALIZE R 2\n\nAPPLY (G_H R".to_string()).unwrap_err();
+        assert_eq!(err.line(), Some(4));
+
+        let ast = parse_numbered("\nINITIALIZE R 2\n\nTENSOR_N U G_H 2".to_string()).unwrap();
+        let lines: Vec<Option<usize>> = ast.0.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, vec![Some(2), Some(4), Some(4)]);
+    }
+
+    #[test]
+    fn test_parse_repeat() {
+        let ast = parse("REPEAT 2 {\nU TENSOR U G_H\nREPEAT 0 {\nMEASURE R RES\n}\n}".to_string());
+        let tensor = ASTNode::VariableAssignment(
+            "U".to_string(),
+            MemoryLocation::Heap,
+            Rc::new(ASTNode::FunctionApplication(
+                "TENSOR".to_string(),
+                vec![
+                    ASTNode::Identifier("U".to_string()),
+                    ASTNode::Literal("G_H".to_string()),
+                ],
+            )),
+        );
+        let measure = ASTNode::VariableAssignment(
+            "RES".to_string(),
+            MemoryLocation::Measurement,
+            Rc::new(ASTNode::FunctionApplication(
+                "MEASURE".to_string(),
+                vec![ASTNode::Identifier("R".to_string())],
+            )),
+        );
+        assert_eq!(
+            ast.unwrap(),
+            vec![ASTNode::Repeat(
+                2,
+                NumberedAST(vec![
+                    (Some(2), tensor),
+                    (
+                        Some(3),
+                        ASTNode::Repeat(0, NumberedAST(vec![(Some(4), measure)]))
+                    ),
+                ])
+            )]
+        );
+
+        let err = parse("REPEAT 2 {\nU TENSOR U G_H".to_string()).unwrap_err();
+        assert_eq!(err.line(), Some(1));
+        let err = parse("U TENSOR G_H G_H\n}".to_string()).unwrap_err();
+        assert_eq!(err.line(), Some(2));
+        assert!(parse("REPEAT -1 {\n}".to_string()).is_err());
+        assert!(parse("REPEAT N {\n}".to_string()).is_err());
+    }
 }
@@ -1,6 +1,15 @@
+use std::{collections::HashMap, error, fmt};
+
 use rand::{thread_rng, Rng};
 
-use crate::{c, matrix::{complex::C, matrix::Matrix}, util::{f64_equal, index_to_binary_string}};
+use crate::{
+    c,
+    matrix::{
+        complex::C,
+        matrix::{pauli_x, pauli_y, pauli_z, Matrix},
+    },
+    util::{f64_equal, index_to_binary_string},
+};
 
 pub fn prob_at(m: &Matrix, idx: usize) -> f64 {
     if (idx >= m.data.len()) || (m.data[0].len() != 1) {
@@ -13,37 +22,232 @@ pub fn prob_at(m: &Matrix, idx: usize) -> f64 {
     val.powf(2.0) / norm.powf(2.0)
 }
 
+fn checked_qbit_length(size: usize) -> Option<usize> {
+    let qbit_len = (size as f64).log2().round() as usize;
+
+    if !f64_equal(qbit_len as f64, (size as f64).log2()) {
+        return None;
+    }
+
+    Some(qbit_len)
+}
+
 pub fn qbit_length(m: &Matrix) -> usize {
-    let qbit_len = (m.size().0 as f64).log2().round() as usize;
+    match checked_qbit_length(m.size().0) {
+        Some(qbit_len) if m.is_vector() => qbit_len,
+        _ => panic!("Invalid input for MEASURE, should be a vector of size power of two"),
+    }
+}
 
-    if !m.is_vector() || !f64_equal(qbit_len as f64, (m.size().0 as f64).log2()) {
-        panic!("Invalid input for MEASURE, should be a vector of size power of two");
+pub fn operator_qbit_length(m: &Matrix) -> Option<usize> {
+    // Number of qbits a square operator acts on, None if it is not a valid gate
+    if m.size().0 != m.size().1 {
+        return None;
     }
 
-    qbit_len
+    checked_qbit_length(m.size().0)
 }
 
-pub fn measure_vec(m: &Matrix) -> String {
+#[derive(Debug, Clone, PartialEq)]
+pub struct DistributionReport {
+    // (bitstring, |amplitude|^2 / norm^2)
+    pub probabilities: Vec<(String, f64)>,
+    // (bitstring, |amplitude|^2) without dividing by the norm
+    pub raw_weights: Vec<(String, f64)>,
+    // Sum of the raw weights, the squared norm of the state
+    pub total_weight: f64,
+}
+
+impl DistributionReport {
+    pub fn is_normalized(&self) -> bool {
+        f64_equal(self.total_weight, 1.0)
+    }
+}
+
+// Exact (bitstring, probability) of every basis state, in index order
+pub fn state_probabilities(m: &Matrix) -> Vec<(String, f64)> {
+    let qbit_len = qbit_length(m);
+    (0..m.size().0)
+        .map(|i| (index_to_binary_string(i, qbit_len), prob_at(m, i)))
+        .collect()
+}
+
+pub fn measure_distribution_report(m: &Matrix) -> DistributionReport {
+    let probabilities = state_probabilities(m);
+    let raw_weights = probabilities
+        .iter()
+        .enumerate()
+        .map(|(i, (key, _))| (key.clone(), m.data[i][0].modulus().powf(2.0)))
+        .collect::<Vec<(String, f64)>>();
+
+    let total_weight = raw_weights.iter().map(|(_, w)| w).sum();
+
+    DistributionReport {
+        probabilities,
+        raw_weights,
+        total_weight,
+    }
+}
+
+pub fn most_likely_outcome(m: &Matrix) -> (String, f64) {
+    // Highest probability basis state, ties are broken by the lowest index
     let qbit_len = qbit_length(m);
-    let mut rng = thread_rng();
-    let val: f64 = rng.gen();
 
-    let mut sum = 0.0;
+    let mut best = (0, prob_at(m, 0));
+    for i in 1..m.size().0 {
+        let prob = prob_at(m, i);
+        if prob > best.1 && !f64_equal(prob, best.1) {
+            best = (i, prob);
+        }
+    }
 
-    let mut pick = 0;
-    for i in 0..m.size().0 {
-        sum += prob_at(m, i);
+    (index_to_binary_string(best.0, qbit_len), best.1)
+}
+
+pub fn fidelity(a: &Matrix, b: &Matrix) -> f64 {
+    // |<a|b>|^2 for pure states, normalized so unnormalized inputs are compared by direction
+    assert!(
+        a.is_vector() && b.is_vector() && a.size() == b.size(),
+        "Invalid input for fidelity, should be two vectors of equal size"
+    );
+
+    let overlap = a.inner_product(b).modulus();
+    overlap.powf(2.0) / (a.norm().powf(2.0) * b.norm().powf(2.0))
+}
+
+pub fn trace_distance(a: &Matrix, b: &Matrix) -> f64 {
+    // For pure states the trace distance reduces to sqrt(1 - F)
+    (1.0 - fidelity(a, b)).max(0.0).sqrt()
+}
+
+pub fn expectation_value(state: &Matrix, observable: &Matrix) -> C {
+    // <psi|O|psi>, real for a Hermitian observable and a normalized state
+    assert!(
+        state.is_vector() && observable.size() == (state.size().0, state.size().0),
+        "Invalid input for expectation_value, should be a vector and a square observable of matching size"
+    );
+
+    state.inner_product(&observable.multiply(state))
+}
+
+// (<X>, <Y>, <Z>) of a single qbit state on the Bloch sphere
+pub fn bloch_vector(state: &Matrix) -> (f64, f64, f64) {
+    assert!(
+        state.is_vector() && state.size().0 == 2,
+        "Invalid input for bloch_vector, should be a single qbit state"
+    );
+
+    (
+        expectation_value(state, &pauli_x()).a,
+        expectation_value(state, &pauli_y()).a,
+        expectation_value(state, &pauli_z()).a,
+    )
+}
+
+pub fn purity(rho: &Matrix) -> f64 {
+    // Tr(rho^2): 1 for pure states, down to 1 / d for the maximally mixed state
+    assert_eq!(
+        rho.size().0,
+        rho.size().1,
+        "Invalid input for purity, should be a square density matrix"
+    );
+
+    (rho.clone() * rho.clone()).trace().a
+}
+
+pub fn measure_vec(m: &Matrix) -> String {
+    measure_vec_seeded(m, &mut thread_rng())
+}
+
+pub fn measure_vec_seeded(m: &Matrix, rng: &mut impl Rng) -> String {
+    index_to_binary_string(measure_index(m, rng), qbit_length(m))
+}
+
+// The sampled outcome together with the probability it had of being measured
+pub fn measure_vec_with_prob(m: &Matrix, rng: &mut impl Rng) -> (String, f64) {
+    let pick = measure_index(m, rng);
+    let outcome = index_to_binary_string(pick, qbit_length(m));
+    (outcome, prob_at(m, pick))
+}
+
+fn measure_index(m: &Matrix, rng: &mut impl Rng) -> usize {
+    // Scale by the total so non-normalized states are sampled proportionally
+    let probs = (0..m.size().0).map(|i| prob_at(m, i)).collect::<Vec<f64>>();
+    let total: f64 = probs.iter().sum();
+    let val = rng.gen::<f64>() * total;
+
+    let mut sum = 0.0;
+    for (i, prob) in probs.iter().enumerate() {
+        sum += prob;
 
         if val < sum {
-            pick = i;
-            break;
+            return i;
+        }
+    }
+
+    // Rounding can leave val just above the final sum, pick the last reachable outcome
+    probs.iter().rposition(|prob| *prob > 0.0).unwrap_or(0)
+}
+
+// Precomputed cumulative distribution of a state vector for repeated sampling: building it
+// is O(N) once, after which every shot is a O(log N) binary search instead of a O(N) sweep
+pub struct Sampler {
+    cumulative: Vec<f64>,
+    qbit_len: usize,
+}
+
+impl Sampler {
+    pub fn new(m: &Matrix) -> Sampler {
+        assert!(m.is_vector(), "Invalid input sampler, should be a vector");
+
+        let mut sum = 0.0;
+        let cumulative = m
+            .data
+            .iter()
+            .map(|row| {
+                sum += row[0].modulus().powf(2.0);
+                sum
+            })
+            .collect::<Vec<f64>>();
+
+        Sampler {
+            cumulative,
+            qbit_len: qbit_length(m),
         }
     }
 
-    return index_to_binary_string(pick, qbit_len);
+    pub fn sample(&self, rng: &mut impl Rng) -> String {
+        let total = self.cumulative[self.cumulative.len() - 1];
+        let val = rng.gen::<f64>() * total;
+
+        let pick = self
+            .cumulative
+            .partition_point(|sum| *sum <= val)
+            .min(self.cumulative.len() - 1);
+        index_to_binary_string(pick, self.qbit_len)
+    }
+}
+
+// Empirical outcome histogram of `shots` measurements, each on a fresh copy of the state
+pub fn measure_distribution(
+    m: &Matrix,
+    shots: usize,
+    rng: &mut impl Rng,
+) -> HashMap<String, usize> {
+    let sampler = Sampler::new(m);
+
+    let mut counts = HashMap::new();
+    for _ in 0..shots {
+        *counts.entry(sampler.sample(rng)).or_insert(0) += 1;
+    }
+    counts
 }
 
 pub fn measure_partial_vec(m: &Matrix, from: i32, to: i32) -> Matrix {
+    measure_partial_vec_seeded(m, from, to, &mut thread_rng())
+}
+
+pub fn measure_partial_vec_seeded(m: &Matrix, from: i32, to: i32, rng: &mut impl Rng) -> Matrix {
     assert!(m.is_vector(), "Invalid input measure, should be a vector");
 
     // GENERATE OPTIONS
@@ -55,21 +259,25 @@ pub fn measure_partial_vec(m: &Matrix, from: i32, to: i32) -> Matrix {
     let qbit_len = qbit_length(m);
 
     // GET PROBABILITIES FOR OPTIONS
+    let norm_sq = m.norm().powi(2);
     for i in 0..m.size().0 {
         let qbinary = index_to_binary_string(i, qbit_len);
         for j in 0..option_vector_size {
             let qbinary_selection = index_to_binary_string(j, size);
             if qbinary[from as usize..to as usize] == qbinary_selection {
-                options.data[j][0] = m.data[i][0] + options.data[j][0];
+                // Accumulate probabilities, summing amplitudes would let them cancel out
+                options.data[j][0] =
+                    c!(options.data[j][0].a + m.data[i][0].modulus().powi(2) / norm_sq);
             }
         }
     }
 
-    print!("Options: {:?}", options);
+    for option in options.data.iter_mut() {
+        option[0] = c!(option[0].a.sqrt());
+    }
 
     // COLLAPSE STATE
-    let res = measure_vec(&options);
-    println!("Res {:?}", res);
+    let res = measure_vec_seeded(&options, rng);
 
     // UPDATE ORIGINAL STATE
     for i in 0..m.size().0 {
@@ -79,12 +287,167 @@ pub fn measure_partial_vec(m: &Matrix, from: i32, to: i32) -> Matrix {
         }
     }
 
-    res_matrix
+    // The surviving amplitudes only carry the probability of the measured outcome
+    res_matrix.renormalized()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StateVectorError {
+    NotAVector((usize, usize)),
+    InvalidSize(usize),
+    NotNormalized(f64),
+}
+
+impl fmt::Display for StateVectorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateVectorError::NotAVector((rows, cols)) => {
+                write!(f, "Expected a vector, got a {}x{} matrix", rows, cols)
+            }
+            StateVectorError::InvalidSize(size) => {
+                write!(f, "State vector size {} is not a power of two", size)
+            }
+            StateVectorError::NotNormalized(norm) => {
+                write!(f, "State vector should have norm 1, got {}", norm)
+            }
+        }
+    }
+}
+
+impl error::Error for StateVectorError {}
+
+// A normalized column vector over a whole number of qbits, checked once at construction
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateVector(Matrix);
+
+impl StateVector {
+    pub fn new(m: Matrix) -> Result<StateVector, StateVectorError> {
+        if !m.is_vector() {
+            return Err(StateVectorError::NotAVector(m.size()));
+        }
+        if checked_qbit_length(m.size().0).is_none() {
+            return Err(StateVectorError::InvalidSize(m.size().0));
+        }
+        if !f64_equal(m.norm(), 1.0) {
+            return Err(StateVectorError::NotNormalized(m.norm()));
+        }
+
+        Ok(StateVector(m))
+    }
+
+    pub fn matrix(&self) -> &Matrix {
+        &self.0
+    }
+
+    pub fn into_matrix(self) -> Matrix {
+        self.0
+    }
+
+    pub fn num_qubits(&self) -> usize {
+        qbit_length(&self.0)
+    }
+
+    pub fn amplitude(&self, idx: usize) -> C {
+        self.0.data[idx][0]
+    }
+
+    pub fn prob_at(&self, idx: usize) -> f64 {
+        prob_at(&self.0, idx)
+    }
+
+    pub fn measure(&self, rng: &mut impl Rng) -> String {
+        measure_vec_seeded(&self.0, rng)
+    }
+
+    // Collapses the qbits from..to, the remaining state is renormalized
+    pub fn measure_partial(&self, from: i32, to: i32, rng: &mut impl Rng) -> StateVector {
+        StateVector(measure_partial_vec_seeded(&self.0, from, to, rng))
+    }
+}
+
+// Gate application on a StateVector, so operators and states cannot be swapped around
+pub trait ApplyGate {
+    fn apply(&self, state: &StateVector) -> StateVector;
+}
+
+impl ApplyGate for Matrix {
+    fn apply(&self, state: &StateVector) -> StateVector {
+        debug_assert!(
+            self.is_unitary(),
+            "Only unitary gates keep a state vector normalized"
+        );
+        StateVector(self.multiply(&state.0))
+    }
+}
+
+// Density operator rho, for mixed states and reduced states of subsystems
+#[derive(Debug, Clone, PartialEq)]
+pub struct DensityMatrix(Matrix);
+
+impl DensityMatrix {
+    pub fn from_state(state: &StateVector) -> DensityMatrix {
+        // |psi><psi|
+        DensityMatrix(state.0.outer_product(&state.0))
+    }
+
+    pub fn matrix(&self) -> &Matrix {
+        &self.0
+    }
+
+    pub fn num_qubits(&self) -> usize {
+        checked_qbit_length(self.0.size().0).expect("Density matrix over a whole number of qbits")
+    }
+
+    pub fn purity(&self) -> f64 {
+        purity(&self.0)
+    }
+
+    // Traces out the listed qbits, the remaining ones keep their relative order
+    pub fn partial_trace(&self, qubits: &[usize]) -> DensityMatrix {
+        let n_qubits = self.num_qubits();
+        assert!(
+            qubits
+                .iter()
+                .enumerate()
+                .all(|(i, q)| *q < n_qubits && !qubits[..i].contains(q)),
+            "Traced qbits should be distinct and inside the register"
+        );
+
+        let kept: Vec<usize> = (0..n_qubits).filter(|q| !qubits.contains(q)).collect();
+        // Full register index with the bits of value spread over the given qbits
+        let scatter = |positions: &[usize], value: usize| {
+            positions.iter().enumerate().fold(0, |index, (i, q)| {
+                let bit = (value >> (positions.len() - 1 - i)) & 1;
+                index | (bit << (n_qubits - 1 - q))
+            })
+        };
+
+        let size = 1 << kept.len();
+        let mut reduced = Matrix::zero_sq(size);
+        for a in 0..size {
+            for b in 0..size {
+                let (row, col) = (scatter(&kept, a), scatter(&kept, b));
+                let mut sum = c!(0);
+                for t in 0..(1 << qubits.len()) {
+                    let traced = scatter(qubits, t);
+                    sum += self.0.data[row | traced][col | traced];
+                }
+                reduced.set_mut(a, b, sum);
+            }
+        }
+        DensityMatrix(reduced)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::mat;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{
+        mat,
+        matrix::matrix::{hadamard_layer, uniform_superposition},
+        util::binary_string_to_int,
+    };
 
     use super::*;
 
@@ -110,19 +473,333 @@ mod tests {
         assert!(res == "10" || res == "11");
     }
 
+    #[test]
+    fn test_qbit_length() {
+        assert_eq!(qbit_length(&Matrix::zero(8, 1)), 3);
+        assert_eq!(qbit_length(&mat![c!(1)]), 0);
+
+        assert_eq!(operator_qbit_length(&Matrix::identity(4)), Some(2));
+        assert_eq!(operator_qbit_length(&Matrix::identity(1)), Some(0));
+        assert_eq!(operator_qbit_length(&Matrix::identity(3)), None);
+        assert_eq!(operator_qbit_length(&Matrix::zero(4, 2)), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_qbit_length_operator_panic() {
+        qbit_length(&Matrix::identity(4));
+    }
+
+    #[test]
+    fn test_measure_distribution_report() {
+        let m = mat![c!(1.0); c!(0.0); c!(0.0); c!(1.0)];
+        let report = measure_distribution_report(&m);
+
+        assert!(!report.is_normalized());
+        assert!(f64_equal(report.total_weight, m.norm().powf(2.0)));
+        assert!(f64_equal(report.total_weight, 2.0));
+        assert_eq!(report.raw_weights[3], ("11".to_string(), 1.0));
+        assert_eq!(report.probabilities[3].0, "11");
+        assert!(f64_equal(report.probabilities[3].1, 0.5));
+
+        let total_prob: f64 = report.probabilities.iter().map(|(_, p)| p).sum();
+        assert!(f64_equal(total_prob, 1.0));
+
+        let normalized = measure_distribution_report(&m.normalized().unwrap());
+        assert!(normalized.is_normalized());
+    }
+
+    #[test]
+    fn test_most_likely_outcome() {
+        // A single Grover iteration on 2 qbits fully amplifies the marked state |10>
+        let s = uniform_superposition(2);
+        let oracle = Matrix::identity(4).set(2, 2, c!(-1));
+        let diffusion = (s.clone() * s.adjoint()).scalar_mul(c!(2)) - Matrix::identity(4);
+
+        let amplified = diffusion * (oracle * s);
+        let (outcome, prob) = most_likely_outcome(&amplified);
+
+        assert_eq!(outcome, "10");
+        assert!(f64_equal(prob, 1.0));
+
+        let (outcome, prob) = most_likely_outcome(&uniform_superposition(2));
+        assert_eq!(outcome, "00");
+        assert!(f64_equal(prob, 0.25));
+    }
+
+    #[test]
+    fn test_fidelity() {
+        let zero = mat![c!(1.0); c!(0.0)];
+        let one = mat![c!(0.0); c!(1.0)];
+        let plus = mat![c!(1.0); c!(1.0)].normalized().unwrap();
+
+        assert!(f64_equal(fidelity(&zero, &zero), 1.0));
+        assert!(f64_equal(fidelity(&zero, &one), 0.0));
+        assert!(f64_equal(fidelity(&zero, &plus), 0.5));
+
+        assert!(f64_equal(trace_distance(&zero, &zero), 0.0));
+        assert!(f64_equal(trace_distance(&zero, &one), 1.0));
+        assert!(f64_equal(trace_distance(&zero, &plus), 0.5_f64.sqrt()));
+    }
+
+    #[test]
+    fn test_purity() {
+        let zero = mat![c!(1.0); c!(0.0)];
+        let zero_density = zero.clone() * zero.adjoint();
+        assert!(f64_equal(purity(&zero_density), 1.0));
+
+        let plus = mat![c!(1.0); c!(1.0)].normalized().unwrap();
+        assert!(f64_equal(purity(&(plus.clone() * plus.adjoint())), 1.0));
+
+        let mixed = Matrix::identity(2).scalar_mul(c!(0.5));
+        assert!(f64_equal(purity(&mixed), 0.5));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_purity_non_square() {
+        purity(&mat![c!(1.0); c!(0.0)]);
+    }
+
+    #[test]
+    fn test_measure_vec_seeded() {
+        let m = uniform_superposition(3);
+        let outcomes = |seed: u64| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..50)
+                .map(|_| measure_vec_seeded(&m, &mut rng))
+                .collect::<Vec<String>>()
+        };
+
+        assert_eq!(outcomes(42), outcomes(42));
+        assert_ne!(outcomes(42), outcomes(43));
+        assert_eq!(measure_vec(&m).len(), 3);
+    }
+
+    #[test]
+    fn test_measure_distribution() {
+        let plus_plus = hadamard_layer(2).multiply(&Matrix::from_basis_string("00").unwrap());
+        let shots = 10000;
+        let counts = measure_distribution(&plus_plus, shots, &mut StdRng::seed_from_u64(9));
+
+        assert_eq!(counts.len(), 4);
+        assert_eq!(counts.values().sum::<usize>(), shots);
+        for outcome in ["00", "01", "10", "11"] {
+            let freq = *counts.get(outcome).unwrap() as f64 / shots as f64;
+            assert!((freq - 0.25).abs() < 0.02);
+        }
+    }
+
+    #[test]
+    fn test_state_probabilities() {
+        let bell = mat![c!(1.0); c!(0.0); c!(0.0); c!(1.0)]
+            .normalized()
+            .unwrap();
+        let probs = state_probabilities(&bell);
+
+        let keys: Vec<&str> = probs.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, vec!["00", "01", "10", "11"]);
+        for (key, prob) in &probs {
+            let expected = if key == "00" || key == "11" { 0.5 } else { 0.0 };
+            assert!(f64_equal(*prob, expected));
+        }
+    }
+
+    #[test]
+    fn test_expectation_value() {
+        let zero = Matrix::from_basis_string("0").unwrap();
+        let plus = hadamard_layer(1).multiply(&zero);
+
+        assert_eq!(expectation_value(&zero, &pauli_z()), c!(1));
+        assert_eq!(expectation_value(&plus, &pauli_z()), c!(0));
+        assert_eq!(expectation_value(&plus, &pauli_x()), c!(1));
+    }
+
+    #[test]
+    fn test_bloch_vector() {
+        let h = std::f64::consts::FRAC_1_SQRT_2;
+        let cardinal = [
+            (mat![c!(1.0); c!(0.0)], (0.0, 0.0, 1.0)),
+            (mat![c!(0.0); c!(1.0)], (0.0, 0.0, -1.0)),
+            (mat![c!(h); c!(h)], (1.0, 0.0, 0.0)),
+            (mat![c!(h); c!(-h)], (-1.0, 0.0, 0.0)),
+            (mat![c!(h); c!(0.0, h)], (0.0, 1.0, 0.0)),
+            (mat![c!(h); c!(0.0, -h)], (0.0, -1.0, 0.0)),
+        ];
+
+        for (state, (x, y, z)) in cardinal {
+            let (bx, by, bz) = bloch_vector(&state);
+            assert!(f64_equal(bx, x) && f64_equal(by, y) && f64_equal(bz, z));
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_expectation_value_size_mismatch() {
+        expectation_value(&uniform_superposition(2), &pauli_z());
+    }
+
+    #[test]
+    fn test_sampler_matches_measure_vec() {
+        let m = mat![c!(0.1); c!(0.5); c!(0.0); c!(0.3, 0.4); c!(0.2); c!(0.0); c!(0.6); c!(0.1)];
+        let sampler = Sampler::new(&m);
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut reference_rng = StdRng::seed_from_u64(7);
+        for _ in 0..1000 {
+            assert_eq!(
+                sampler.sample(&mut rng),
+                measure_vec_seeded(&m, &mut reference_rng)
+            );
+        }
+    }
+
+    #[test]
+    fn test_measure_vec_with_prob() {
+        let m = mat![c!(0.1); c!(0.0); c!(0.3); c!(0.9)];
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut reference_rng = StdRng::seed_from_u64(11);
+
+        for _ in 0..100 {
+            let (outcome, prob) = measure_vec_with_prob(&m, &mut rng);
+            assert_eq!(outcome, measure_vec_seeded(&m, &mut reference_rng));
+            assert_eq!(prob, prob_at(&m, binary_string_to_int(outcome)));
+            assert!(prob > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_measure_distribution_bell_state() {
+        let m = mat![c!(1.0); c!(0.0); c!(0.0); c!(1.0)];
+        let shots = 10000;
+        let counts = measure_distribution(&m, shots, &mut StdRng::seed_from_u64(3));
+
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts.values().sum::<usize>(), shots);
+        let zeros = *counts.get("00").unwrap() as f64 / shots as f64;
+        assert!((zeros - 0.5).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_measure_non_normalized() {
+        let m = mat![c!(1.0); c!(1.0); c!(1.0); c!(1.0)];
+        let mut rng = StdRng::seed_from_u64(5);
+
+        let mut counts = HashMap::new();
+        for _ in 0..4000 {
+            *counts.entry(measure_vec_seeded(&m, &mut rng)).or_insert(0) += 1;
+        }
+
+        assert_eq!(counts.len(), 4);
+        for count in counts.values() {
+            assert!((*count as f64 / 4000.0 - 0.25).abs() < 0.05);
+        }
+
+        // The last outcome is still reachable when the probabilities sum to slightly less than 1
+        let m = mat![c!(0.0); c!(0.0); c!(0.0); c!(1.0 - 1e-12)];
+        assert_eq!(measure_vec_seeded(&m, &mut rng), "11");
+    }
+
     #[test]
     fn test_partial_measure() {
-        let m = mat![c!(0.0); c!(1.0); c!(0.7); c!(0.5)];
+        let m = mat![c!(0.0); c!(0.8); c!(0.6); c!(0.0)];
         let res = super::measure_partial_vec(&m, 1, 2);
 
         assert!(
-            res.clone() == mat![c!(0.0); c!(0.0); c!(0.7); c!(0.0)]
-                || res.clone() == mat![c!(0.0); c!(1.0); c!(0.0); c!(0.5)]
+            res.clone() == mat![c!(0.0); c!(0.0); c!(1.0); c!(0.0)]
+                || res.clone() == mat![c!(0.0); c!(1.0); c!(0.0); c!(0.0)]
         );
 
-        let m = mat![c!(1.0); c!(1.0); c!(1.0); c!(1.0)];
+        let m = mat![c!(0.5); c!(0.5); c!(0.5); c!(0.5)];
         let res = super::measure_partial_vec(&m, 0, 2);
         assert_eq!(res.norm(), 1.0);
+
+        // Only the first qbit is 0, leaving [0.6, 0.8] which is renormalized
+        let m = mat![c!(0.6); c!(0.8); c!(0); c!(0)].scalar_mul(c!(0.5));
+        let res = super::measure_partial_vec(&m, 0, 1);
+        assert!(res.is_normalized(1e-9));
+        assert_eq!(res, mat![c!(0.6); c!(0.8); c!(0); c!(0)]);
+
+        // Opposite amplitudes in a block should not cancel each other out
+        let m = mat![c!(0.5); c!(-0.5); c!(0.5); c!(0.5)];
+        let mut rng = StdRng::seed_from_u64(0);
+        let outcomes: Vec<Matrix> = (0..20)
+            .map(|_| super::measure_partial_vec_seeded(&m, 0, 1, &mut rng))
+            .collect();
+        assert!(outcomes.iter().all(|res| res.is_normalized(1e-9)));
+        assert!(outcomes.iter().any(|res| res.data[0][0] != c!(0)));
+        assert!(outcomes.iter().any(|res| res.data[0][0] == c!(0)));
     }
 
+    #[test]
+    fn test_state_vector() {
+        let bell = mat![c!(1.0); c!(0.0); c!(0.0); c!(1.0)];
+        let bell = bell.normalized().unwrap();
+        let state = StateVector::new(bell.clone()).unwrap();
+
+        assert_eq!(state.num_qubits(), 2);
+        assert!(f64_equal(state.prob_at(3), 0.5));
+        assert_eq!(state.matrix(), &bell);
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let outcome = state.measure(&mut rng);
+        assert!(outcome == "00" || outcome == "11");
+
+        let collapsed = state.measure_partial(0, 1, &mut rng);
+        assert!(f64_equal(collapsed.matrix().norm(), 1.0));
+        assert!(f64_equal(collapsed.prob_at(0) + collapsed.prob_at(3), 1.0));
+        assert!(f64_equal(collapsed.prob_at(0) * collapsed.prob_at(3), 0.0));
+    }
+
+    #[test]
+    fn test_state_vector_apply() {
+        let zero = StateVector::new(Matrix::from_basis_string("00").unwrap()).unwrap();
+        let plus_plus = hadamard_layer(2).apply(&zero);
+
+        assert_eq!(plus_plus.num_qubits(), 2);
+        for idx in 0..4 {
+            assert_eq!(plus_plus.amplitude(idx), c!(0.5));
+        }
+        assert_eq!(plus_plus.into_matrix(), uniform_superposition(2));
+    }
+
+    #[test]
+    fn test_density_matrix_partial_trace() {
+        let bell = mat![c!(1.0); c!(0.0); c!(0.0); c!(1.0)];
+        let bell = StateVector::new(bell.normalized().unwrap()).unwrap();
+        let rho = DensityMatrix::from_state(&bell);
+
+        assert!(f64_equal(rho.purity(), 1.0));
+        assert!(rho.matrix().is_valid_density_matrix(1e-9));
+
+        let maximally_mixed = Matrix::identity(2).scalar_mul(c!(0.5));
+        for qubit in 0..2 {
+            let reduced = rho.partial_trace(&[qubit]);
+            assert_eq!(reduced.matrix(), &maximally_mixed);
+            assert!(f64_equal(reduced.purity(), 0.5));
+        }
+
+        // A product state stays pure, and the order of the kept qbits is preserved
+        let product = StateVector::new(Matrix::from_basis_string("011").unwrap()).unwrap();
+        let reduced = DensityMatrix::from_state(&product).partial_trace(&[1]);
+        assert!(f64_equal(reduced.purity(), 1.0));
+        assert_eq!(reduced.matrix().get(1, 1), c!(1));
+        assert_eq!(reduced.num_qubits(), 2);
+    }
+
+    #[test]
+    fn test_state_vector_invalid() {
+        assert_eq!(
+            StateVector::new(Matrix::identity(2)),
+            Err(StateVectorError::NotAVector((2, 2)))
+        );
+        assert_eq!(
+            StateVector::new(mat![c!(1.0); c!(0.0); c!(0.0)]),
+            Err(StateVectorError::InvalidSize(3))
+        );
+        assert!(matches!(
+            StateVector::new(mat![c!(1.0); c!(1.0)]),
+            Err(StateVectorError::NotNormalized(_))
+        ));
+    }
 }
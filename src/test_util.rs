@@ -0,0 +1,53 @@
+#[macro_export]
+macro_rules! assert_matrix_approx_eq {
+    ($a: expr, $b: expr, $eps: expr) => {{
+        let (a, b, eps) = (&$a, &$b, $eps as f64);
+        assert_eq!(a.size(), b.size(), "Matrix sizes differ");
+
+        // (difference, row, col) of the worst entry
+        let mut max_diff = (0.0_f64, 0, 0);
+        for i in 0..a.data.len() {
+            for j in 0..a.data[0].len() {
+                let diff = (a.data[i][j] - b.data[i][j]).modulus();
+                if diff > max_diff.0 || diff.is_nan() {
+                    max_diff = (diff, i, j);
+                }
+            }
+        }
+
+        let (diff, row, col) = max_diff;
+        if diff > eps || diff.is_nan() {
+            panic!(
+                "Matrices differ by {:e} (eps {:e}) at ({}, {}): {:?} != {:?}",
+                diff, eps, row, col, a.data[row][col], b.data[row][col]
+            );
+        }
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        c, mat,
+        matrix::{complex::C, matrix::Matrix},
+    };
+
+    #[test]
+    fn test_approx_eq_passes() {
+        let a = mat!(c!(1), c!(0); c!(0), c!(1.0 / 3.0));
+        let b = mat!(c!(1), c!(0); c!(0), c!(1.0) / c!(3));
+        assert_matrix_approx_eq!(a, b, 1e-12);
+
+        let a = mat!(c!(1.0, 0.0000001));
+        let b = mat!(c!(1));
+        assert_matrix_approx_eq!(a, b, 1e-6);
+    }
+
+    #[test]
+    #[should_panic(expected = "at (1, 0)")]
+    fn test_approx_eq_fails() {
+        let a = mat!(c!(1); c!(0.5));
+        let b = mat!(c!(1); c!(0.6));
+        assert_matrix_approx_eq!(a, b, 1e-6);
+    }
+}
@@ -1,3 +1,53 @@
+use alloc::string::String;
+
+// Float math is only provided by std, without it the same methods are backed by libm
+#[cfg(not(any(feature = "std", test)))]
+pub trait FloatExt {
+    fn sqrt(self) -> f64;
+    fn sin(self) -> f64;
+    fn cos(self) -> f64;
+    fn atan2(self, other: f64) -> f64;
+    fn log2(self) -> f64;
+    fn exp(self) -> f64;
+    fn ln(self) -> f64;
+    fn ceil(self) -> f64;
+}
+
+#[cfg(not(any(feature = "std", test)))]
+impl FloatExt for f64 {
+    fn sqrt(self) -> f64 {
+        libm::sqrt(self)
+    }
+
+    fn sin(self) -> f64 {
+        libm::sin(self)
+    }
+
+    fn cos(self) -> f64 {
+        libm::cos(self)
+    }
+
+    fn atan2(self, other: f64) -> f64 {
+        libm::atan2(self, other)
+    }
+
+    fn log2(self) -> f64 {
+        libm::log2(self)
+    }
+
+    fn exp(self) -> f64 {
+        libm::exp(self)
+    }
+
+    fn ln(self) -> f64 {
+        libm::log(self)
+    }
+
+    fn ceil(self) -> f64 {
+        libm::ceil(self)
+    }
+}
+
 pub fn f64_equal(a: f64, b: f64) -> bool {
     (a - b).abs() < 0.000000001
 }
@@ -37,7 +87,6 @@ pub fn index_to_binary_string(index: usize, n: usize) -> String {
     result
 }
 
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -57,4 +106,20 @@ mod tests {
         assert_eq!(binary_string_to_int("00000".to_string()), 0);
         assert_eq!(binary_string_to_int("0001".to_string()), 1);
     }
+
+    #[test]
+    fn test_index_to_binary_string() {
+        assert_eq!(index_to_binary_string(5, 3), "101");
+        assert_eq!(index_to_binary_string(1, 4), "0001");
+        assert_eq!(index_to_binary_string(0, 0), "");
+
+        // Round trip in both directions, padded to the requested width
+        for n in 1..=6 {
+            for index in 0..(1 << n) {
+                let s = index_to_binary_string(index, n);
+                assert_eq!(s.len(), n);
+                assert_eq!(binary_string_to_int(s), index);
+            }
+        }
+    }
 }